@@ -0,0 +1,455 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::ll1::{Driver, Error, Machine, Recovery};
+use crate::value::Value;
+use crate::EAction::{self, *};
+use crate::ENonTerminal::{self, Start};
+use crate::ETerminal::{self, *};
+use crate::{at_end, internal, place, table, CalcError, EPop, Lexer, Position};
+use std::fmt;
+use std::io::Read;
+
+/// Operators with two operands
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Op {
+    Add,
+    Subtract,
+    Times,
+    Divide,
+    Power,
+    Approx,
+}
+
+/// An expression as written, before evaluation
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    /// A number, date or text
+    Num(Value),
+    /// A unit, by name
+    Unit(String),
+    /// A variable
+    Name(String),
+    Neg(Box<Expr>),
+    BinOp(Op, Box<Expr>, Box<Expr>),
+    /// A percentage, as in '15%'
+    Percent(Box<Expr>),
+    Array(Vec<Expr>),
+    Call(String, Vec<Expr>),
+    /// if(condition, then, else)
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// sum or prod of 'body' for 'name' from 'lo' to 'hi'
+    Series {
+        product: bool,
+        name: String,
+        lo: Box<Expr>,
+        hi: Box<Expr>,
+        body: Box<Expr>,
+    },
+    /// diff or solve of 'body' in 'name', at or starting from 'point'
+    Apply {
+        function: String,
+        body: Box<Expr>,
+        name: String,
+        point: Box<Expr>,
+    },
+    /// The previous result
+    Ans,
+    /// The memory register
+    Memory,
+}
+
+/// What one line of input does
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Line {
+    /// Print the value of an expression
+    Expr(Expr),
+    MemAdd(Expr),
+    MemSub(Expr),
+    MemClear,
+    /// A ':' command with its arguments
+    Command(String, Vec<Expr>),
+}
+
+/// Builds trees with the same parse table that Calc evaluates with
+struct Builder<R> {
+    driver: Driver<ETerminal, ENonTerminal, EAction>,
+    lexer: Lexer<R>,
+    lexeme: ETerminal,
+    value: Value,
+
+    // Most recently matched terminal, for Push to tell what it was
+    matched: ETerminal,
+
+    // Trees built so far, with the depth at the start of each array or
+    // argument list, and the functions awaiting their arguments
+    nodes: Vec<Expr>,
+    marks: Vec<usize>,
+    calls: Vec<String>,
+
+    // Where each '(' yet to be closed was
+    opened: Vec<Position>,
+
+    lines: Vec<Line>,
+    error: Option<CalcError>,
+}
+
+impl<R: Read> Builder<R> {
+    fn pop(&mut self) -> Result<Box<Expr>, String> {
+        Ok(Box::new(self.nodes.epop()?))
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let Expr::Name(name) = self.nodes.epop()? else {
+            return Err(internal("expected a name"));
+        };
+        Ok(name)
+    }
+
+    fn binary(&mut self, op: Op) -> Result<Expr, String> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        Ok(Expr::BinOp(op, a, b))
+    }
+
+    fn text(&self) -> Result<String, String> {
+        let Value::Text(text) = &self.value else {
+            return Err(internal("expected a name"));
+        };
+        Ok(text.clone())
+    }
+}
+
+impl<R: Read> Machine for Builder<R> {
+    type T = ETerminal;
+    type N = ENonTerminal;
+    type A = EAction;
+
+    fn driver(&mut self) -> &mut Driver<ETerminal, ENonTerminal, EAction> {
+        &mut self.driver
+    }
+
+    fn peek(&mut self) -> ETerminal {
+        if self.lexeme == NONE {
+            (self.lexeme, self.value) = self.lexer.lex();
+        }
+        self.lexeme
+    }
+
+    fn consume(&mut self) {
+        match self.lexeme {
+            OP => self.opened.push(self.lexer.position()),
+            CP => {
+                self.opened.pop();
+            }
+            _ => {}
+        }
+        self.matched = self.lexeme;
+        self.lexeme = NONE;
+    }
+
+    fn recover(&mut self, error: Error<ETerminal>, _goal: ENonTerminal) -> Recovery {
+        self.error = Some(place(
+            at_end(error, self.lexer.ended()),
+            self.lexer.position(),
+            &self.value,
+            self.opened.last().copied(),
+        ));
+        Recovery::End
+    }
+
+    fn action(&mut self, action: EAction) -> Result<(), String> {
+        let node = match action {
+            Push => match self.matched {
+                NAME | APPLY | COMMAND => Expr::Name(self.text()?),
+                _ => Expr::Num(self.value.clone()),
+            },
+            Variable => Expr::Name(self.text()?),
+            Unit => Expr::Unit(self.lexer.name.clone()),
+            Name => Expr::Name(self.lexer.name.clone()),
+            Negate => Expr::Neg(self.pop()?),
+            Percent => Expr::Percent(self.pop()?),
+            Add => self.binary(Op::Add)?,
+            Subtract => self.binary(Op::Subtract)?,
+            Times => self.binary(Op::Times)?,
+            Divide => self.binary(Op::Divide)?,
+            Power => self.binary(Op::Power)?,
+            Approx => self.binary(Op::Approx)?,
+            Ans => Expr::Ans,
+            Recall => Expr::Memory,
+            Mark => {
+                self.marks.push(self.nodes.len());
+                return Ok(());
+            }
+            Array => {
+                let mark = self.marks.epop()?;
+                Expr::Array(self.nodes.esplit_off(mark)?)
+            }
+            Begin => {
+                self.calls.push(self.text()?);
+                self.marks.push(self.nodes.len());
+                return Ok(());
+            }
+            Call => {
+                let mark = self.marks.epop()?;
+                Expr::Call(self.calls.epop()?, self.nodes.esplit_off(mark)?)
+            }
+            Else => {
+                let otherwise = self.pop()?;
+                let then = self.pop()?;
+                Expr::If(self.pop()?, then, otherwise)
+            }
+            // The end of a sum or prod, with its name and bounds under
+            // the body
+            Next => {
+                let body = self.pop()?;
+                let hi = self.pop()?;
+                let lo = self.pop()?;
+                let name = self.name()?;
+                self.marks.epop()?;
+                Expr::Series {
+                    product: self.calls.epop()? == "prod",
+                    name,
+                    lo,
+                    hi,
+                    body,
+                }
+            }
+            Apply => {
+                let point = self.pop()?;
+                let name = self.name()?;
+                let body = self.pop()?;
+                Expr::Apply {
+                    function: self.name()?,
+                    body,
+                    name,
+                    point,
+                }
+            }
+            Print => {
+                let e = self.nodes.epop()?;
+                self.lines.push(Line::Expr(e));
+                return Ok(());
+            }
+            MemAdd | MemSub | MemClear => {
+                let line = match action {
+                    MemAdd => Line::MemAdd(self.nodes.epop()?),
+                    MemSub => Line::MemSub(self.nodes.epop()?),
+                    _ => Line::MemClear,
+                };
+                self.lines.push(line);
+                return Ok(());
+            }
+            Command => {
+                let mark = self.marks.epop()?;
+                let args = self.nodes.esplit_off(mark)?;
+                let name = self.name()?;
+                self.lines.push(Line::Command(name, args));
+                return Ok(());
+            }
+            // Evaluating needs these to know when to skip and replay
+            // tokens; building a tree doesn't
+            Cond | Then | Body | Hold | Held => return Ok(()),
+        };
+        self.nodes.push(node);
+        Ok(())
+    }
+}
+
+/// Parse each line of 'input' into a tree instead of evaluating it,
+/// stopping at the first error
+pub fn parse<R: Read>(input: R) -> Result<Vec<Line>, CalcError> {
+    let mut builder = Builder {
+        driver: Driver::new(table()),
+        lexer: Lexer::new(input),
+        lexeme: NONE,
+        value: Value::number(0.0),
+        matched: NONE,
+        nodes: Vec::new(),
+        marks: Vec::new(),
+        calls: Vec::new(),
+        opened: Vec::new(),
+        lines: Vec::new(),
+        error: None,
+    };
+    builder.run(Start);
+    match builder.error {
+        Some(error) => Err(error),
+        None => Ok(builder.lines),
+    }
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Subtract => "-",
+            Op::Times => "*",
+            Op::Divide => "/",
+            Op::Power => "^",
+            Op::Approx => "~=",
+        }
+    }
+}
+
+impl Expr {
+    /// How tightly this binds, for deciding where parentheses go
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::BinOp(Op::Approx, _, _) => 0,
+            Expr::BinOp(Op::Add | Op::Subtract, _, _) => 1,
+            Expr::BinOp(Op::Times | Op::Divide, _, _) => 2,
+            Expr::Neg(_) => 3,
+            Expr::BinOp(Op::Power, _, _) => 4,
+            Expr::Percent(_) => 5,
+            _ => 6,
+        }
+    }
+
+    /// Write 'self', in parentheses if it binds less tightly than 'min'
+    fn operand(&self, f: &mut fmt::Formatter, min: u8) -> fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+/// Write expressions separated by commas
+fn list(f: &mut fmt::Formatter, exprs: &[Expr]) -> fmt::Result {
+    for (i, e) in exprs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", e)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Num(Value::Text(text)) => write!(f, "\"{}\"", text),
+            Expr::Num(value) => write!(f, "{}", value),
+            Expr::Unit(name) | Expr::Name(name) => write!(f, "{}", name),
+            Expr::Neg(a) => {
+                write!(f, "-")?;
+                a.operand(f, 3)
+            }
+            // Units follow what they multiply, as in '3 m^2'
+            Expr::BinOp(Op::Times, a, b)
+                if matches!(**b, Expr::Unit(_))
+                    || matches!(&**b, Expr::BinOp(Op::Power, u, _) if matches!(**u, Expr::Unit(_))) =>
+            {
+                a.operand(f, 2)?;
+                write!(f, " {}", b)
+            }
+            Expr::BinOp(Op::Power, a, b) => {
+                a.operand(f, 6)?;
+                write!(f, "^")?;
+                b.operand(f, 3)
+            }
+            Expr::BinOp(op, a, b) => {
+                let p = self.precedence();
+                a.operand(f, p.max(1))?;
+                write!(f, " {} ", op.symbol())?;
+                b.operand(f, p + 1)
+            }
+            Expr::Percent(a) => {
+                a.operand(f, 6)?;
+                write!(f, "%")
+            }
+            Expr::Array(elements) => {
+                write!(f, "[")?;
+                list(f, elements)?;
+                write!(f, "]")
+            }
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                list(f, args)?;
+                write!(f, ")")
+            }
+            Expr::If(c, t, e) => write!(f, "if({}, {}, {})", c, t, e),
+            Expr::Series {
+                product,
+                name,
+                lo,
+                hi,
+                body,
+            } => {
+                let function = if *product { "prod" } else { "sum" };
+                write!(f, "{}({}, {}, {}, {})", function, name, lo, hi, body)
+            }
+            Expr::Apply {
+                function,
+                body,
+                name,
+                point,
+            } => write!(f, "{}({}, {}, {})", function, body, name, point),
+            // As the lexer spells the latest result
+            Expr::Ans => write!(f, "$1"),
+            Expr::Memory => write!(f, "MR"),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Expr(e) => write!(f, "{}", e),
+            Line::MemAdd(Expr::Ans) => write!(f, "M+"),
+            Line::MemAdd(e) => write!(f, "M+ {}", e),
+            Line::MemSub(e) => write!(f, "M- {}", e),
+            Line::MemClear => write!(f, "MC"),
+            // Leading names are words, and the rest a list
+            Line::Command(name, args) => {
+                write!(f, ":{}", name)?;
+                let words = args
+                    .iter()
+                    .take_while(|arg| matches!(arg, Expr::Name(_)))
+                    .count();
+                for word in &args[..words] {
+                    write!(f, " {}", word)?;
+                }
+                if words < args.len() {
+                    write!(f, " ")?;
+                    list(f, &args[words..])?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_previous_result_parses_back() {
+        let sum = Expr::BinOp(Op::Add, Box::new(Expr::Ans), Box::new(Expr::Memory));
+        assert_eq!(sum.to_string(), "$1 + MR");
+        let lines = parse(sum.to_string().as_bytes()).unwrap();
+        assert_eq!(lines[0].to_string(), "$1 + MR");
+        assert_eq!(parse("M+\n".as_bytes()).unwrap(), [Line::MemAdd(Expr::Ans)]);
+    }
+}
@@ -0,0 +1,86 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+/// Height of each glyph in rows
+const HEIGHT: usize = 5;
+
+/// Large glyphs for the characters that make up numbers
+const FONT: &[(char, [&str; HEIGHT])] = &[
+    ('0', ["###", "# #", "# #", "# #", "###"]),
+    ('1', [" # ", "## ", " # ", " # ", "###"]),
+    ('2', ["###", "  #", "###", "#  ", "###"]),
+    ('3', ["###", "  #", "###", "  #", "###"]),
+    ('4', ["# #", "# #", "###", "  #", "  #"]),
+    ('5', ["###", "#  ", "###", "  #", "###"]),
+    ('6', ["###", "#  ", "###", "# #", "###"]),
+    ('7', ["###", "  #", "  #", "  #", "  #"]),
+    ('8', ["###", "# #", "###", "# #", "###"]),
+    ('9', ["###", "# #", "###", "  #", "###"]),
+    ('.', [" ", " ", " ", " ", "#"]),
+    (',', ["  ", "  ", "  ", " #", "# "]),
+    ('-', ["   ", "   ", "###", "   ", "   "]),
+    ('+', ["   ", " # ", "###", " # ", "   "]),
+    ('e', ["   ", "###", "# #", "## ", "###"]),
+    ('/', ["  #", "  #", " # ", "#  ", "#  "]),
+    ('^', [" # ", "# #", "   ", "   ", "   "]),
+    ('[', ["##", "# ", "# ", "# ", "##"]),
+    (']', ["##", " #", " #", " #", "##"]),
+    (' ', ["  ", "  ", "  ", "  ", "  "]),
+];
+
+/// Render text in large characters. Anything without a glyph, like
+/// unit names, is drawn at normal size on the middle row.
+pub fn render(text: &str) -> String {
+    let mut rows = vec![String::new(); HEIGHT];
+    for c in text.chars() {
+        match FONT.iter().find(|(g, _)| *g == c) {
+            Some((_, glyph)) => {
+                for (row, line) in rows.iter_mut().zip(glyph) {
+                    row.push_str(line);
+                    row.push(' ');
+                }
+            }
+            None => {
+                for (r, row) in rows.iter_mut().enumerate() {
+                    row.push(if r == HEIGHT / 2 { c } else { ' ' });
+                }
+            }
+        }
+    }
+    let mut out = String::new();
+    for row in rows {
+        out.push_str(row.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_digits_in_rows() {
+        assert_eq!(render("12"), " #  ###\n##    #\n #  ###\n #  #\n### ###\n");
+    }
+
+    #[test]
+    fn puts_other_characters_on_the_middle_row() {
+        assert_eq!(render("1m"), " #\n##\n #  m\n #\n###\n");
+    }
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+//! Make the parse table from calc.grammar, as a static array for
+//! lib.rs to include. Grammar names are upper case for terminals, and otherwise
+//! non-terminals when they have productions or else actions.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "ll1.rs"]
+mod ll1;
+
+use ll1::Token::{self, Action, NonTerminal, Terminal};
+
+type Rule<'a> = (&'a str, Vec<&'a str>, bool);
+
+fn symbol<'a>(rules: &[Rule<'a>], name: &'a str) -> Token<&'a str, &'a str, &'a str> {
+    if name.chars().all(|c| c.is_ascii_uppercase()) {
+        Terminal(name)
+    } else if rules.iter().any(|(lhs, _, _)| *lhs == name) {
+        NonTerminal(name)
+    } else {
+        Action(name)
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=calc.grammar");
+    println!("cargo:rerun-if-changed=ll1.rs");
+    let text = fs::read_to_string("calc.grammar").expect("calc.grammar");
+
+    let mut rules: Vec<Rule> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (lhs, rhs) = line
+            .split_once("->")
+            .unwrap_or_else(|| panic!("calc.grammar:{}: expected '->'", number + 1));
+        for alternative in rhs.split('|') {
+            let alternative = alternative.trim();
+            let (alternative, preferred) = match alternative.strip_prefix('!') {
+                Some(alternative) => (alternative, true),
+                None => (alternative, false),
+            };
+            let names: Vec<&str> = alternative
+                .split_whitespace()
+                .filter(|name| *name != "ε")
+                .collect();
+            rules.push((lhs.trim(), names, preferred));
+        }
+    }
+
+    let productions: Vec<ll1::Production<&str, &str, &str>> = rules
+        .iter()
+        .map(|(lhs, names, preferred)| {
+            (
+                *lhs,
+                names.iter().map(|n| symbol(&rules, n)).collect(),
+                *preferred,
+            )
+        })
+        .collect();
+    let table = ll1::build(&productions, "END").unwrap_or_else(|e| panic!("calc.grammar: {}", e));
+
+    let mut entries: Vec<_> = table.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let size = "ETerminal::ALL.len() * ENonTerminal::ALL.len()";
+    let mut out = format!("static TABLE: [Option<&[Token]>; {}] = {{\n", size);
+    out.push_str(&format!(
+        "    let mut table: [Option<&[Token]>; {0}] = [None; {0}];\n",
+        size
+    ));
+    for ((t, n), tokens) in entries {
+        let tokens: Vec<String> = tokens
+            .iter()
+            .map(|token| match token {
+                Terminal(name) => format!("Terminal({})", name),
+                NonTerminal(name) => format!("NonTerminal({})", name),
+                Action(name) => format!("Action({})", name),
+            })
+            .collect();
+        out.push_str(&format!(
+            "    table[{} as usize * ETerminal::ALL.len() + {} as usize] = Some(&[{}]);\n",
+            n,
+            t,
+            tokens.join(", ")
+        ));
+    }
+    out.push_str("    table\n};\n");
+    let path = Path::new(&env::var("OUT_DIR").expect("OUT_DIR")).join("table.rs");
+    fs::write(path, out).expect("table.rs");
+}
@@ -0,0 +1,738 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::date;
+use crate::interrupted;
+use crate::primes;
+use crate::radix;
+use crate::random::Rng;
+use crate::rational::{gcd, Rational};
+use crate::symbolic::Poly;
+use crate::units::{Quantity, NONE};
+use crate::value::Value;
+use std::rc::Rc;
+
+/// No upper limit on the number of arguments
+const MANY: usize = usize::MAX;
+
+/// A function callable as 'name(args)'
+pub struct Builtin {
+    pub name: &'static str,
+    /// Minimum and maximum number of arguments
+    pub args: (usize, usize),
+    /// How to call it and what it does, for ':help'
+    pub help: &'static str,
+    /// Called with the arguments and the calculator's random numbers
+    pub func: fn(&[Value], &mut Rng) -> Result<Value, String>,
+}
+
+/// A function a program has added, taking and returning plain numbers
+pub type Registered = Rc<dyn Fn(&[f64]) -> f64>;
+
+/// A function being called, built in or added
+pub enum Callee {
+    Builtin(&'static Builtin),
+    Registered(Registered),
+}
+
+/// Fetch an argument which must be a plain integer
+pub fn integer(arg: &Value) -> Result<i128, String> {
+    if let Value::Scalar(q) = arg {
+        if q.dim == NONE {
+            if let Some(n) = q.exact.and_then(|e| e.as_integer()) {
+                return Ok(n);
+            }
+            if q.val.fract() == 0.0 && q.val.abs() < i64::MAX as f64 {
+                return Ok(q.val as i128);
+            }
+        }
+    }
+    Err(format!("expected an integer, not {}", arg))
+}
+
+/// Fetch an argument which must be a non-negative integer
+fn natural(arg: &Value) -> Result<u64, String> {
+    u64::try_from(integer(arg)?).map_err(|_| format!("{} is out of range", arg))
+}
+
+/// The representation of a number in another radix, as text
+fn in_radix(arg: &Value, radix: u32) -> Result<Value, String> {
+    match arg {
+        Value::Scalar(q) => Ok(Value::Text(radix::format(q, radix)?)),
+        _ => Err(String::from("expected a number")),
+    }
+}
+
+/// Fetch an argument as a 64-bit register value; negative numbers
+/// are taken in two's complement
+fn bits(arg: &Value) -> Result<u64, String> {
+    let n = integer(arg)?;
+    if let Ok(n) = u64::try_from(n) {
+        Ok(n)
+    } else if let Ok(n) = i64::try_from(n) {
+        Ok(n as u64)
+    } else {
+        Err(format!("{} does not fit in 64 bits", arg))
+    }
+}
+
+/// Fetch a bit number or shift count
+fn bit_index(arg: &Value) -> Result<u32, String> {
+    match integer(arg)? {
+        n @ 0..=63 => Ok(n as u32),
+        n => Err(format!("bit {} is outside 0..63", n)),
+    }
+}
+
+pub fn int_value(n: i128) -> Value {
+    Value::Scalar(whole(n))
+}
+
+/// An exact whole number
+fn whole(n: i128) -> Quantity {
+    Quantity::rational(Rational::integer(n))
+}
+
+/// Apply a real function to a plain number, in radians for angles
+fn real(arg: &Value, f: fn(f64) -> f64) -> Result<Value, String> {
+    match arg {
+        Value::Scalar(q) if q.dim == NONE => {
+            let v = f(q.val);
+            if v.is_nan() && !q.val.is_nan() {
+                Err(format!("{} is out of range", arg))
+            } else {
+                Ok(Value::number(v))
+            }
+        }
+        _ => Err(format!("expected a plain number, not {}", arg)),
+    }
+}
+
+/// Fetch an argument as a polynomial, where numbers are constants
+fn polynomial(arg: &Value) -> Result<Poly, String> {
+    match arg {
+        Value::Symbolic(p) => Ok(p.clone()),
+        Value::Scalar(q) => Ok(Poly::constant(*q)),
+        _ => Err(format!("expected a polynomial, not {}", arg)),
+    }
+}
+
+/// Aggregates take either a single array or a list of numbers
+fn series(args: &[Value]) -> Result<Vec<Quantity>, String> {
+    match args {
+        [Value::Vector(v)] => Ok(v.iter().map(|x| Quantity::new(*x)).collect()),
+        [Value::Matrix(m)] => Ok(m.iter().flatten().map(|x| Quantity::new(*x)).collect()),
+        _ => args
+            .iter()
+            .map(|a| match a {
+                Value::Scalar(q) => Ok(*q),
+                _ => Err(String::from("expected a list or numbers")),
+            })
+            .collect(),
+    }
+}
+
+fn sum(args: &[Value]) -> Result<Quantity, String> {
+    let series = series(args)?;
+    match series.split_first() {
+        Some((first, rest)) => rest.iter().try_fold(*first, |a, b| a.add(*b)),
+        None => Ok(Quantity::rational(Rational::integer(0))),
+    }
+}
+
+/// Floored division, returning the quotient and a remainder with the
+/// sign of the divisor. Works on any real numbers in matching units.
+fn divmod(args: &[Value]) -> Result<(Quantity, Quantity), String> {
+    let (Value::Scalar(a), Value::Scalar(b)) = (&args[0], &args[1]) else {
+        return Err(String::from("expected numbers"));
+    };
+    if b.val == 0.0 {
+        return Err(String::from("division by zero"));
+    }
+    let q = a.divide(*b)?;
+    if q.dim != NONE {
+        return Err(String::from("cannot divide values with different units"));
+    }
+    let q = Quantity {
+        exact: q.exact.map(|e| e.floor()),
+        ..Quantity::new(q.val.floor())
+    };
+    Ok((q, a.subtract(b.times(q)?)?))
+}
+
+/// Ways to choose k of n, exact until it outgrows an i128
+fn choose(n: u64, k: u64) -> Result<Quantity, String> {
+    if k > n {
+        return Ok(whole(0));
+    }
+    let k = k.min(n - k) as i128;
+    let n = n as i128;
+    let mut exact = Some(1i128);
+    let mut approx = 1.0;
+    for i in 1..=k {
+        interrupted()?;
+        let m = n - k + i;
+        // r * m / i is whole, so i / gcd(r, i) divides m
+        exact = exact.and_then(|r| {
+            let g = gcd(r, i);
+            (r / g).checked_mul(m / (i / g))
+        });
+        approx = approx * m as f64 / i as f64;
+        // Each step at least doubles it, so once it is too big for an
+        // f64 there's no use going on
+        if exact.is_none() && approx.is_infinite() {
+            break;
+        }
+    }
+    Ok(exact.map_or(Quantity::new(approx.round()), whole))
+}
+
+/// Ordered ways to pick k of n
+fn permute(n: u64, k: u64) -> Result<Quantity, String> {
+    if k > n {
+        return Ok(whole(0));
+    }
+    let mut exact = Some(1i128);
+    let mut approx = 1.0;
+    for m in n - k + 1..=n {
+        interrupted()?;
+        exact = exact.and_then(|r| r.checked_mul(m as i128));
+        approx *= m as f64;
+        if exact.is_none() && approx.is_infinite() {
+            break;
+        }
+    }
+    Ok(exact.map_or(Quantity::new(approx), whole))
+}
+
+/// Pick the value which 'better' prefers over all others
+fn extreme(args: &[Value], better: fn(f64, f64) -> bool) -> Result<Value, String> {
+    let series = series(args)?;
+    let Some((first, rest)) = series.split_first() else {
+        return Err(String::from("no values to compare"));
+    };
+    let mut pick = *first;
+    for q in rest {
+        if q.dim != pick.dim {
+            return Err(String::from("cannot compare values with different units"));
+        }
+        if better(q.val, pick.val) {
+            pick = *q;
+        }
+    }
+    Ok(Value::Scalar(pick))
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "acos",
+        args: (1, 1),
+        help: "acos(x): inverse cosine, in radians",
+        func: |args, _| real(&args[0], f64::acos),
+    },
+    Builtin {
+        name: "acosh",
+        args: (1, 1),
+        help: "acosh(x): inverse hyperbolic cosine",
+        func: |args, _| real(&args[0], f64::acosh),
+    },
+    Builtin {
+        name: "asin",
+        args: (1, 1),
+        help: "asin(x): inverse sine, in radians",
+        func: |args, _| real(&args[0], f64::asin),
+    },
+    Builtin {
+        name: "asinh",
+        args: (1, 1),
+        help: "asinh(x): inverse hyperbolic sine",
+        func: |args, _| real(&args[0], f64::asinh),
+    },
+    Builtin {
+        name: "atan",
+        args: (1, 1),
+        help: "atan(x): inverse tangent, in radians",
+        func: |args, _| real(&args[0], f64::atan),
+    },
+    Builtin {
+        name: "atan2",
+        args: (2, 2),
+        help: "atan2(y, x): angle of the point (x, y), in radians",
+        func: |args, _| {
+            // Both sides in the same units, which cancel
+            let (Value::Scalar(y), Value::Scalar(x)) = (&args[0], &args[1]) else {
+                return Err(String::from("atan2 needs numbers"));
+            };
+            if y.dim != x.dim {
+                return Err(String::from("atan2 needs both sides in the same units"));
+            }
+            Ok(Value::number(y.val.atan2(x.val)))
+        },
+    },
+    Builtin {
+        name: "atanh",
+        args: (1, 1),
+        help: "atanh(x): inverse hyperbolic tangent",
+        func: |args, _| real(&args[0], f64::atanh),
+    },
+    Builtin {
+        name: "bin",
+        args: (1, 1),
+        help: "bin(n): n written in binary",
+        func: |args, _| in_radix(&args[0], 2),
+    },
+    Builtin {
+        name: "binomial",
+        args: (2, 3),
+        help:
+            "binomial(n, k[, p]): ways to choose k of n, or with p the chance of k successes in n",
+        func: |args, _| {
+            let (n, k) = (natural(&args[0])?, natural(&args[1])?);
+            let ways = choose(n, k)?;
+            let Some(p) = args.get(2) else {
+                return Ok(Value::Scalar(ways));
+            };
+            // With a probability, the chance of exactly k successes in n
+            let p = match p {
+                Value::Scalar(q) if q.dim == NONE && (0.0..=1.0).contains(&q.val) => q.val,
+                _ => return Err(String::from("probability must be between 0 and 1")),
+            };
+            let chance = p.powf(k as f64) * (1.0 - p).powf(n.saturating_sub(k) as f64);
+            Ok(Value::number(ways.val * chance))
+        },
+    },
+    Builtin {
+        name: "bit",
+        args: (2, 2),
+        help: "bit(n, i): bit i of n",
+        func: |args, _| {
+            Ok(int_value(
+                (bits(&args[0])? >> bit_index(&args[1])? & 1) as i128,
+            ))
+        },
+    },
+    Builtin {
+        name: "check",
+        args: (1, 1),
+        help: "check(x): text giving x as an f64 beside its exact value",
+        func: |args, _| {
+            let Value::Scalar(q) = args[0] else {
+                return Err(String::from("check needs a number"));
+            };
+            let text = match q.exact {
+                Some(exact) => {
+                    // Measure the difference exactly when the f64 allows
+                    let diff = Rational::from_f64(q.val)
+                        .and_then(|f| f.subtract(exact))
+                        .map_or(q.val - exact.to_f64(), |d| d.to_f64());
+                    format!("f64 {}, exact {}, difference {}", q.val, exact, diff)
+                }
+                None => format!("f64 {}, no exact value", q.val),
+            };
+            Ok(Value::Text(text))
+        },
+    },
+    Builtin {
+        name: "clz",
+        args: (1, 1),
+        help: "clz(n): leading zero bits in 64-bit n",
+        func: |args, _| Ok(int_value(bits(&args[0])?.leading_zeros() as i128)),
+    },
+    Builtin {
+        name: "coeffs",
+        args: (1, 1),
+        help: "coeffs(p): coefficients of polynomial p",
+        func: |args, _| {
+            let c = polynomial(&args[0])?.coefficients()?;
+            Value::array(&c.into_iter().map(Value::Scalar).collect::<Vec<_>>())
+        },
+    },
+    Builtin {
+        name: "cosh",
+        args: (1, 1),
+        help: "cosh(x): hyperbolic cosine",
+        func: |args, _| real(&args[0], f64::cosh),
+    },
+    Builtin {
+        name: "ctz",
+        args: (1, 1),
+        help: "ctz(n): trailing zero bits in 64-bit n",
+        func: |args, _| Ok(int_value(bits(&args[0])?.trailing_zeros() as i128)),
+    },
+    Builtin {
+        name: "date",
+        args: (1, 1),
+        help: "date(text): the date written as text, as in date(\"2024-06-01\")",
+        func: |args, _| match &args[0] {
+            Value::Text(t) => match date::parse(t) {
+                Some(t) => Ok(Value::Date(t)),
+                None => Err(format!("invalid date {}", t)),
+            },
+            Value::Date(t) => Ok(Value::Date(*t)),
+            _ => Err(String::from("date needs text like \"2024-06-01\"")),
+        },
+    },
+    Builtin {
+        name: "dec",
+        args: (1, 1),
+        help: "dec(n): n written in decimal",
+        func: |args, _| in_radix(&args[0], 10),
+    },
+    Builtin {
+        name: "det",
+        args: (1, 1),
+        help: "det(m): determinant of matrix m",
+        func: |args, _| args[0].det(),
+    },
+    Builtin {
+        name: "divmod",
+        args: (2, 2),
+        help: "divmod(a, b): quotient and remainder of a / b",
+        func: |args, _| {
+            let (q, r) = divmod(args)?;
+            Value::array(&[Value::Scalar(q), Value::Scalar(r)])
+        },
+    },
+    Builtin {
+        name: "factor",
+        args: (1, 1),
+        help: "factor(n): prime factors of n",
+        func: |args, _| {
+            let n = natural(&args[0])?;
+            if n < 2 {
+                return Err(format!("cannot factor {}", n));
+            }
+            let factors = primes::factor(n)?;
+            Ok(Value::Vector(factors.iter().map(|p| *p as f64).collect()))
+        },
+    },
+    Builtin {
+        name: "gcd",
+        args: (2, MANY),
+        help: "gcd(a, b, ...): greatest common divisor",
+        func: |args, _| {
+            let mut g = 0;
+            for arg in args {
+                g = gcd(g, integer(arg)?);
+            }
+            Ok(int_value(g))
+        },
+    },
+    Builtin {
+        name: "hex",
+        args: (1, 1),
+        help: "hex(n): n written in hexadecimal",
+        func: |args, _| in_radix(&args[0], 16),
+    },
+    Builtin {
+        name: "inv",
+        args: (1, 1),
+        help: "inv(m): inverse of matrix m",
+        func: |args, _| args[0].inv(),
+    },
+    Builtin {
+        name: "isprime",
+        args: (1, 1),
+        help: "isprime(n): 1 if n is prime, else 0",
+        func: |args, _| {
+            let n = integer(&args[0])?;
+            let prime = u64::try_from(n).is_ok_and(primes::is_prime);
+            Ok(int_value(prime as i128))
+        },
+    },
+    Builtin {
+        name: "lcm",
+        args: (2, MANY),
+        help: "lcm(a, b, ...): least common multiple",
+        func: |args, _| {
+            let mut l = 1;
+            for arg in args {
+                let n = integer(arg)?;
+                if n == 0 {
+                    return Ok(int_value(0));
+                }
+                l = (l / gcd(l, n))
+                    .checked_mul(n.abs())
+                    .ok_or_else(|| String::from("lcm overflow"))?;
+            }
+            Ok(int_value(l))
+        },
+    },
+    Builtin {
+        name: "len",
+        args: (0, MANY),
+        help: "len(a, ...): how many numbers there are",
+        func: |args, _| Ok(int_value(series(args)?.len() as i128)),
+    },
+    Builtin {
+        name: "log",
+        args: (1, 2),
+        help: "log(x[, base]): logarithm, natural unless base is given",
+        func: |args, _| {
+            let Some(base) = args.get(1) else {
+                return real(&args[0], f64::ln);
+            };
+            // log10 and log2 are exact at powers of their bases
+            match base {
+                Value::Scalar(q) if q.dim == NONE && q.val == 10.0 => real(&args[0], f64::log10),
+                Value::Scalar(q) if q.dim == NONE && q.val == 2.0 => real(&args[0], f64::log2),
+                _ => real(&args[0], f64::ln)?.divide(real(base, f64::ln)?),
+            }
+        },
+    },
+    Builtin {
+        name: "max",
+        args: (1, MANY),
+        help: "max(a, ...): largest",
+        func: |args, _| extreme(args, |a, b| a > b),
+    },
+    Builtin {
+        name: "mean",
+        args: (1, MANY),
+        help: "mean(a, ...): average",
+        func: |args, _| {
+            let n = series(args)?.len();
+            if n == 0 {
+                return Err(String::from("mean of an empty list"));
+            }
+            let n = Quantity::rational(Rational::integer(n as i128));
+            Ok(Value::Scalar(sum(args)?.divide(n)?))
+        },
+    },
+    Builtin {
+        name: "min",
+        args: (1, MANY),
+        help: "min(a, ...): smallest",
+        func: |args, _| extreme(args, |a, b| a < b),
+    },
+    Builtin {
+        name: "mod",
+        args: (2, 2),
+        help: "mod(a, b): remainder of a / b",
+        func: |args, _| Ok(Value::Scalar(divmod(args)?.1)),
+    },
+    Builtin {
+        name: "ncr",
+        args: (2, 2),
+        help: "ncr(n, k): combinations of k from n",
+        func: |args, _| {
+            Ok(Value::Scalar(choose(
+                natural(&args[0])?,
+                natural(&args[1])?,
+            )?))
+        },
+    },
+    Builtin {
+        name: "nextprime",
+        args: (1, 1),
+        help: "nextprime(n): smallest prime above n",
+        func: |args, _| {
+            let n = integer(&args[0])?.max(0);
+            let n = u64::try_from(n).map_err(|_| format!("{} is out of range", args[0]))?;
+            match primes::next_prime(n) {
+                Some(p) => Ok(int_value(p as i128)),
+                None => Err(format!("no prime after {} below 2^64", n)),
+            }
+        },
+    },
+    Builtin {
+        name: "now",
+        args: (0, 0),
+        help: "now(): the current date and time",
+        func: |_, _| Ok(Value::Date(date::now())),
+    },
+    Builtin {
+        name: "npr",
+        args: (2, 2),
+        help: "npr(n, k): permutations of k from n",
+        func: |args, _| {
+            Ok(Value::Scalar(permute(
+                natural(&args[0])?,
+                natural(&args[1])?,
+            )?))
+        },
+    },
+    Builtin {
+        name: "oct",
+        args: (1, 1),
+        help: "oct(n): n written in octal",
+        func: |args, _| in_radix(&args[0], 8),
+    },
+    Builtin {
+        name: "poly",
+        args: (1, 1),
+        help: "poly(v): polynomial in x with coefficients v",
+        func: |args, _| match &args[0] {
+            Value::Vector(c) => Ok(Value::Symbolic(Poly::from_coefficients("x", c)?)),
+            _ => Err(String::from("poly needs a vector of coefficients")),
+        },
+    },
+    Builtin {
+        name: "polyval",
+        args: (2, 2),
+        help: "polyval(p, x): polynomial p at x",
+        func: |args, _| {
+            let Value::Scalar(x) = args[1] else {
+                return Err(String::from("polyval needs a number to evaluate at"));
+            };
+            Ok(Value::Scalar(polynomial(&args[0])?.at(x)?))
+        },
+    },
+    Builtin {
+        name: "popcount",
+        args: (1, 1),
+        help: "popcount(n): bits set in 64-bit n",
+        func: |args, _| Ok(int_value(bits(&args[0])?.count_ones() as i128)),
+    },
+    Builtin {
+        name: "prod",
+        args: (1, MANY),
+        help: "prod(a, ...): product; prod(i, lo, hi, body) multiplies body over i",
+        func: |args, _| {
+            let p = series(args)?
+                .into_iter()
+                .try_fold(Quantity::rational(Rational::integer(1)), |a, b| a.times(b))?;
+            Ok(Value::Scalar(p))
+        },
+    },
+    Builtin {
+        name: "rand",
+        args: (0, 0),
+        help: "rand(): random number from 0 up to 1",
+        func: |_, rng| Ok(Value::number(rng.uniform())),
+    },
+    Builtin {
+        name: "randint",
+        args: (2, 2),
+        help: "randint(lo, hi): random integer from lo to hi",
+        func: |args, rng| {
+            let (lo, hi) = (integer(&args[0])?, integer(&args[1])?);
+            if lo > hi {
+                return Err(format!("randint: {} is greater than {}", lo, hi));
+            }
+            Ok(int_value(rng.range(lo, hi)))
+        },
+    },
+    Builtin {
+        name: "roots",
+        args: (1, 1),
+        help: "roots(p): roots of polynomial p",
+        func: |args, _| Ok(Value::Vector(polynomial(&args[0])?.roots()?)),
+    },
+    Builtin {
+        name: "rotl",
+        args: (2, 2),
+        help: "rotl(n, i): 64-bit n rotated left i bits",
+        func: |args, _| {
+            let x = bits(&args[0])?.rotate_left(bit_index(&args[1])?);
+            Ok(int_value(x as i128))
+        },
+    },
+    Builtin {
+        name: "rotr",
+        args: (2, 2),
+        help: "rotr(n, i): 64-bit n rotated right i bits",
+        func: |args, _| {
+            let x = bits(&args[0])?.rotate_right(bit_index(&args[1])?);
+            Ok(int_value(x as i128))
+        },
+    },
+    Builtin {
+        name: "seed",
+        args: (1, 1),
+        help: "seed(n): start random numbers from n",
+        func: |args, rng| {
+            *rng = Rng::new(integer(&args[0])? as u64);
+            Ok(args[0].clone())
+        },
+    },
+    Builtin {
+        name: "sinh",
+        args: (1, 1),
+        help: "sinh(x): hyperbolic sine",
+        func: |args, _| real(&args[0], f64::sinh),
+    },
+    Builtin {
+        name: "sum",
+        args: (1, MANY),
+        help: "sum(a, ...): total; sum(i, lo, hi, body) adds body over i",
+        func: |args, _| Ok(Value::Scalar(sum(args)?)),
+    },
+    Builtin {
+        name: "tanh",
+        args: (1, 1),
+        help: "tanh(x): hyperbolic tangent",
+        func: |args, _| real(&args[0], f64::tanh),
+    },
+    Builtin {
+        name: "today",
+        args: (0, 0),
+        help: "today(): today's date",
+        func: |_, _| Ok(Value::Date(date::today())),
+    },
+    Builtin {
+        name: "transpose",
+        args: (1, 1),
+        help: "transpose(m): matrix m with rows and columns swapped",
+        func: |args, _| args[0].transpose(),
+    },
+];
+
+/// Find a builtin function by name
+pub fn find(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// Check the argument count and call the function
+pub fn call(builtin: &Builtin, args: &[Value], rng: &mut Rng) -> Result<Value, String> {
+    let (min, max) = builtin.args;
+    if args.len() < min || args.len() > max {
+        let count = if min == max {
+            format!("{}", min)
+        } else if max == MANY {
+            format!("at least {}", min)
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(format!(
+            "{} takes {} argument{}, not {}",
+            builtin.name,
+            count,
+            if max == 1 || (max == MANY && min == 1) {
+                ""
+            } else {
+                "s"
+            },
+            args.len()
+        ));
+    }
+    (builtin.func)(args, rng)
+}
+
+/// Call a function added by a program, whose arguments must be plain
+/// numbers
+pub fn call_registered(func: &Registered, args: &[Value]) -> Result<Value, String> {
+    let mut numbers = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            Value::Scalar(q) if q.dim == NONE => numbers.push(q.val),
+            _ => return Err(format!("expected a plain number, not {}", arg)),
+        }
+    }
+    Ok(Value::number(func(&numbers)))
+}
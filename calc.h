@@ -0,0 +1,52 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+#ifndef CALC_H
+#define CALC_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Calc calc_t;
+
+/* Make a calculator, to be released with calc_free */
+calc_t *calc_new(void);
+
+/* Release a calculator */
+void calc_free(calc_t *calc);
+
+/*
+ * Evaluate a line, storing its value, which must be a plain number, in
+ * *result. Returns 0, or 1 for a line with no value like a command,
+ * or -1 with the error left for calc_error. Settings made by commands
+ * and the memory register carry over from line to line.
+ */
+int calc_eval(calc_t *calc, const char *input, double *result);
+
+/*
+ * The latest error from calc_eval, or NULL if it succeeded. The text
+ * lasts until the next calc_eval or calc_free.
+ */
+const char *calc_error(const calc_t *calc);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CALC_H */
@@ -0,0 +1,253 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::ast::{self, Expr, Op};
+use crate::builtins::{self, Builtin};
+use crate::random::{self, Rng};
+use crate::units;
+use crate::value::Value;
+use crate::{CalcError, Position};
+use std::cell::Cell;
+
+/// Steps of a compiled formula, working on a stack of numbers
+#[derive(Clone)]
+enum Opcode {
+    Push(f64),
+    /// The value bound to the variable with this index
+    Load(usize),
+    Negate,
+    Add,
+    Subtract,
+    Times,
+    Divide,
+    Power,
+    /// Take a hundredth of the top of the stack
+    Percent,
+    /// Add or subtract that share of the value under it
+    AddShare,
+    SubtractShare,
+    /// Call a builtin with this many arguments
+    Call(&'static Builtin, usize),
+    /// Go to the step at this index if the top of the stack is zero
+    JumpIfZero(usize),
+    Jump(usize),
+}
+
+/// A formula of plain numbers, compiled once to be evaluated many times
+/// with different values for its variables, as when plotting
+#[derive(Clone)]
+pub struct Compiled {
+    code: Vec<Opcode>,
+    names: Vec<String>,
+    // Deepest the stack gets
+    depth: usize,
+    // For rand and randint, of its own as each calculator has
+    rng: Cell<Rng>,
+}
+
+impl Compiled {
+    pub fn compile(formula: &str) -> Result<Compiled, CalcError> {
+        let unsupported = |message: String| CalcError::Eval {
+            at: Position {
+                line: 1,
+                column: 1,
+                offset: 0,
+            },
+            message,
+        };
+        let text = format!("{}\n", formula.trim_end_matches('\n'));
+        let expr = match ast::parse(text.as_bytes())?.as_slice() {
+            [ast::Line::Expr(expr)] => expr.clone(),
+            _ => return Err(unsupported(String::from("expected one expression"))),
+        };
+        let mut compiled = Compiled {
+            code: Vec::new(),
+            names: Vec::new(),
+            depth: 0,
+            rng: Cell::new(Rng::new(random::clock())),
+        };
+        compiled.expr(&expr, 0).map_err(unsupported)?;
+        Ok(compiled)
+    }
+
+    /// The variables, in the order their values are passed to eval
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Evaluate with 'values' for the variables, in the order of names()
+    pub fn eval(&self, values: &[f64]) -> Result<f64, String> {
+        if values.len() != self.names.len() {
+            return Err(format!(
+                "expected {} values, not {}",
+                self.names.len(),
+                values.len()
+            ));
+        }
+        let mut stack: Vec<f64> = Vec::with_capacity(self.depth);
+        let mut pc = 0;
+        while let Some(op) = self.code.get(pc) {
+            pc += 1;
+            let v = match op {
+                Opcode::Push(v) => *v,
+                Opcode::Load(i) => values[*i],
+                Opcode::Negate => -stack.pop().unwrap_or_default(),
+                Opcode::Percent => stack.pop().unwrap_or_default() / 100.0,
+                Opcode::Call(builtin, count) => {
+                    let args: Vec<Value> = stack
+                        .drain(stack.len() - count..)
+                        .map(Value::number)
+                        .collect();
+                    let mut rng = self.rng.get();
+                    let result = builtins::call(builtin, &args, &mut rng);
+                    self.rng.set(rng);
+                    match result? {
+                        Value::Scalar(q) if q.dim == units::NONE => q.val,
+                        v => return Err(format!("{} returned {}", builtin.name, v)),
+                    }
+                }
+                Opcode::JumpIfZero(to) => {
+                    if stack.pop().unwrap_or_default() == 0.0 {
+                        pc = *to;
+                    }
+                    continue;
+                }
+                Opcode::Jump(to) => {
+                    pc = *to;
+                    continue;
+                }
+                _ => {
+                    let b = stack.pop().unwrap_or_default();
+                    let a = stack.pop().unwrap_or_default();
+                    match op {
+                        Opcode::Add => a + b,
+                        Opcode::Subtract => a - b,
+                        Opcode::Times => a * b,
+                        Opcode::Divide if b == 0.0 => return Err(String::from("division by zero")),
+                        Opcode::Divide => a / b,
+                        Opcode::Power => a.powf(b),
+                        Opcode::AddShare => a + a * b,
+                        _ => a - a * b,
+                    }
+                }
+            };
+            stack.push(v);
+        }
+        Ok(stack.pop().unwrap_or_default())
+    }
+
+    /// Append the steps for 'expr', which starts with 'depth' values
+    /// already on the stack
+    fn expr(&mut self, expr: &Expr, depth: usize) -> Result<(), String> {
+        self.depth = self.depth.max(depth + 1);
+        match expr {
+            Expr::Num(Value::Scalar(q)) if q.dim == units::NONE => {
+                self.code.push(Opcode::Push(q.val));
+            }
+            // Units can't be compiled, so their names are variables too
+            Expr::Name(name) | Expr::Unit(name) => {
+                let index = match self.names.iter().position(|n| n == name) {
+                    Some(index) => index,
+                    None => {
+                        self.names.push(name.clone());
+                        self.names.len() - 1
+                    }
+                };
+                self.code.push(Opcode::Load(index));
+            }
+            Expr::Neg(a) => {
+                self.expr(a, depth)?;
+                self.code.push(Opcode::Negate);
+            }
+            Expr::Percent(a) => {
+                self.expr(a, depth)?;
+                self.code.push(Opcode::Percent);
+            }
+            Expr::BinOp(op, a, b) => {
+                let share = matches!(**b, Expr::Percent(_));
+                let op = match op {
+                    Op::Add if share => Opcode::AddShare,
+                    Op::Subtract if share => Opcode::SubtractShare,
+                    Op::Add => Opcode::Add,
+                    Op::Subtract => Opcode::Subtract,
+                    Op::Times => Opcode::Times,
+                    Op::Divide => Opcode::Divide,
+                    Op::Power => Opcode::Power,
+                    Op::Approx => return Err(String::from("cannot compile '~='")),
+                };
+                self.expr(a, depth)?;
+                self.expr(b, depth + 1)?;
+                self.code.push(op);
+            }
+            Expr::Call(name, args) => {
+                let builtin =
+                    builtins::find(name).ok_or_else(|| format!("unknown function {}", name))?;
+                for (i, arg) in args.iter().enumerate() {
+                    self.expr(arg, depth + i)?;
+                }
+                self.code.push(Opcode::Call(builtin, args.len()));
+            }
+            // Only the branch taken is evaluated
+            Expr::If(c, t, e) => {
+                self.expr(c, depth)?;
+                let test = self.code.len();
+                self.code.push(Opcode::JumpIfZero(0));
+                self.expr(t, depth)?;
+                let skip = self.code.len();
+                self.code.push(Opcode::Jump(0));
+                self.code[test] = Opcode::JumpIfZero(self.code.len());
+                self.expr(e, depth)?;
+                self.code[skip] = Opcode::Jump(self.code.len());
+            }
+            _ => return Err(format!("cannot compile {}", expr)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_with_values() {
+        let f = Compiled::compile("x^2 + 2*y").unwrap();
+        assert_eq!(f.names(), ["x", "y"]);
+        assert_eq!(f.eval(&[3.0, 1.0]).unwrap(), 11.0);
+        assert_eq!(f.eval(&[0.0, -1.0]).unwrap(), -2.0);
+    }
+
+    #[test]
+    fn conditions_and_builtins() {
+        let f = Compiled::compile("if(x, max(x, 4), 10)").unwrap();
+        assert_eq!(f.eval(&[-4.0]).unwrap(), 4.0);
+        assert_eq!(f.eval(&[5.0]).unwrap(), 5.0);
+        assert_eq!(f.eval(&[0.0]).unwrap(), 10.0);
+    }
+
+    #[test]
+    fn checks_the_number_of_values() {
+        let f = Compiled::compile("a + b").unwrap();
+        assert!(f.eval(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn rejects_more_than_one_expression() {
+        assert!(Compiled::compile("1\n2").is_err());
+    }
+}
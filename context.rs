@@ -0,0 +1,402 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::ast::{self, Expr, Line};
+use crate::format::Style;
+use crate::units::NONE;
+use crate::value::Value;
+use crate::{Calc, CalcError, Position, Snapshot};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::io::{self, Cursor};
+use std::rc::Rc;
+
+/// Builtins which can give a different result each time
+const VARYING: &[&str] = &["now", "rand", "randint", "seed", "today"];
+
+/// Values of lines evaluated before, keyed by the line's expression
+/// tree, written out, along with the values of the names it uses and
+/// the modes that change what it comes to. The oldest go first once
+/// there are 'limit' of them.
+#[derive(Default)]
+struct Cache {
+    limit: usize,
+    values: HashMap<String, Value>,
+    order: VecDeque<String>,
+}
+
+impl Cache {
+    fn insert(&mut self, key: String, value: Value) {
+        if self.order.len() == self.limit {
+            if let Some(oldest) = self.order.pop_front() {
+                self.values.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.values.insert(key, value);
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.order.clear();
+    }
+}
+
+/// A calculator for a program to drive, taking lines as text and
+/// handing back their values without printing anything. Modes are set
+/// the way a person would, as in eval_line(":precision 6"), and stay
+/// set along with the memory register and the previous result.
+pub struct EvalContext {
+    calc: Calc,
+    cache: Cache,
+}
+
+impl EvalContext {
+    pub fn new() -> EvalContext {
+        let mut calc = Calc::new(Box::new(io::empty()));
+        calc.quiet = true;
+        EvalContext {
+            calc,
+            cache: Cache::default(),
+        }
+    }
+
+    /// Give 'name' a value in the lines evaluated from now on
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.calc.variables.insert(String::from(name), value);
+        self.calc.renamed();
+    }
+
+    /// Make 'name(args)' call 'func' in the lines evaluated from now
+    /// on, in place of any builtin of that name. Arguments must be
+    /// plain numbers.
+    pub fn register_fn<F>(&mut self, name: &str, func: F)
+    where
+        F: Fn(&[f64]) -> f64 + 'static,
+    {
+        self.calc.lexer.functions.insert(String::from(name));
+        self.calc
+            .functions
+            .insert(String::from(name), Rc::new(func));
+        self.calc.renamed();
+    }
+
+    /// How this context writes numbers, as set by ':format',
+    /// ':precision' and so on, for showing its values with
+    /// value.styled(context.style())
+    pub fn style(&self) -> &Style {
+        &self.calc.style
+    }
+
+    /// Take what the lines so far have left behind, so restore can
+    /// roll back to it, as for undo
+    pub fn snapshot(&self) -> Snapshot {
+        self.calc.snapshot()
+    }
+
+    /// Go back to a snapshot, forgetting what's happened since
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.calc.restore(snapshot);
+    }
+
+    /// Keep the values of up to 'entries' lines, so evaluating one of
+    /// them again with the same values for its names is just a lookup.
+    /// Lines which use random numbers, the time, the memory register,
+    /// earlier results or functions the program added are always
+    /// evaluated. Zero, as to begin with, keeps none.
+    pub fn cache(&mut self, entries: usize) {
+        self.cache.clear();
+        self.cache.limit = entries;
+    }
+
+    /// Forget the values cached so far
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Evaluate one line, returning its value, or None for a line with
+    /// no value, like a command or a memory key
+    pub fn eval_line(&mut self, line: &str) -> Result<Option<Value>, CalcError> {
+        let key = self.key(line);
+        if let Some(value) = key.as_ref().and_then(|key| self.cache.values.get(key)) {
+            let value = value.clone();
+            self.calc.remember(value.clone());
+            return Ok(Some(value));
+        }
+        let text = format!("{}\n", line.trim_end_matches('\n'));
+        self.calc
+            .read(None, Box::new(Cursor::new(text.into_bytes())));
+        let (results, errors) = (self.calc.results, self.calc.errors);
+        self.calc.lines();
+        match self.calc.last_error.take() {
+            Some(error) if self.calc.errors > errors => Err(error),
+            _ if self.calc.results > results => {
+                let value = self.calc.ans.clone();
+                if let Some(key) = key {
+                    self.cache.insert(key, value.clone());
+                }
+                Ok(Some(value))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// What to cache a line's value under, or None when it's not to
+    /// be cached
+    fn key(&self, line: &str) -> Option<String> {
+        if self.cache.limit == 0 {
+            return None;
+        }
+        let lines = ast::parse(line.as_bytes()).ok()?;
+        let [Line::Expr(expr)] = lines.as_slice() else {
+            return None;
+        };
+        let mut names = Vec::new();
+        if !self.steady(expr, &mut names) {
+            return None;
+        }
+        let mut key = expr.to_string();
+        names.sort();
+        names.dedup();
+        for name in names {
+            if let Some(value) = self.calc.variables.get(name) {
+                let _ = write!(key, "\n{} = {:?}", name, value);
+            }
+        }
+        let _ = write!(key, "\n{:?} {}", self.calc.tolerance, self.calc.symbolic);
+        Some(key)
+    }
+
+    /// Whether 'expr' comes to the same value whenever the names in it
+    /// do, collecting those names
+    fn steady<'a>(&self, expr: &'a Expr, names: &mut Vec<&'a str>) -> bool {
+        match expr {
+            Expr::Num(_) => true,
+            Expr::Unit(name) | Expr::Name(name) => {
+                names.push(name);
+                !name.starts_with('$')
+            }
+            Expr::Neg(a) | Expr::Percent(a) => self.steady(a, names),
+            Expr::BinOp(_, a, b) => self.steady(a, names) && self.steady(b, names),
+            Expr::Array(items) => items.iter().all(|item| self.steady(item, names)),
+            Expr::Call(name, args) => {
+                !VARYING.contains(&name.as_str())
+                    && !self.calc.functions.contains_key(name)
+                    && args.iter().all(|arg| self.steady(arg, names))
+            }
+            Expr::If(c, t, e) => {
+                self.steady(c, names) && self.steady(t, names) && self.steady(e, names)
+            }
+            Expr::Series { lo, hi, body, .. } => {
+                self.steady(lo, names) && self.steady(hi, names) && self.steady(body, names)
+            }
+            Expr::Apply { body, point, .. } => {
+                self.steady(body, names) && self.steady(point, names)
+            }
+            Expr::Ans | Expr::Memory => false,
+        }
+    }
+
+    /// Evaluate a line which must come out as a plain number
+    pub fn eval_number(&mut self, line: &str) -> Result<f64, CalcError> {
+        let message = match self.eval_line(line)? {
+            Some(Value::Scalar(q)) if q.dim == NONE => return Ok(q.val),
+            Some(value) => format!("expected a plain number, not {}", value),
+            None => String::from("nothing to evaluate"),
+        };
+        Err(CalcError::Eval {
+            at: Position {
+                line: 1,
+                column: 1,
+                offset: 0,
+            },
+            message,
+        })
+    }
+
+    /// Evaluate each line of 'text', returning the values of those that
+    /// have them, or the first error
+    pub fn eval_str(&mut self, text: &str) -> Result<Vec<Value>, CalcError> {
+        let mut values = Vec::new();
+        let mut start = 0;
+        for (number, line) in text.split_inclusive('\n').enumerate() {
+            match self.eval_line(line.trim_end_matches(['\r', '\n'])) {
+                Ok(value) => values.extend(value),
+                Err(error) => return Err(error.on_line(number + 1, start)),
+            }
+            start += line.len();
+        }
+        Ok(values)
+    }
+}
+
+impl Default for EvalContext {
+    fn default() -> EvalContext {
+        EvalContext::new()
+    }
+}
+
+/// Evaluate a formula, as in eval("2*(3+4)"), which must come out as
+/// a plain number
+pub fn eval(formula: &str) -> Result<f64, CalcError> {
+    EvalContext::new().eval_number(formula)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::PRECISION;
+
+    #[test]
+    fn keeps_state_between_lines() {
+        let mut context = EvalContext::new();
+        assert_eq!(context.eval_number("2*(3+4)").unwrap(), 14.0);
+        assert_eq!(context.eval_number("$1 + 1").unwrap(), 15.0);
+        assert!(context.eval_line(":precision 6").unwrap().is_none());
+        assert_eq!(context.style().digits, 6);
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        let mut context = EvalContext::new();
+        context.set("r", Value::number(3.0));
+        context.register_fn("twice", |args| args[0] * 2.0);
+        assert_eq!(context.eval_number("twice(r) + 1").unwrap(), 7.0);
+    }
+
+    #[test]
+    fn restores_snapshots() {
+        let mut context = EvalContext::new();
+        context.set("r", Value::number(3.0));
+        context.eval_line("seed(7)").unwrap();
+        context.eval_line("M+ 5").unwrap();
+        context.eval_line("2*3").unwrap();
+        let snapshot = context.snapshot();
+        let roll = context.eval_number("rand()").unwrap();
+        context.set("r", Value::number(4.0));
+        context.register_fn("twice", |args| args[0] * 2.0);
+        context.eval_line(":precision 3").unwrap();
+        context.eval_line("M+ 1").unwrap();
+        context.restore(&snapshot);
+        assert_eq!(context.eval_number("$1").unwrap(), 6.0);
+        assert_eq!(context.eval_number("r + MR").unwrap(), 8.0);
+        assert_eq!(context.style().digits, PRECISION);
+        assert!(context.eval_line("twice(1)").is_err());
+        context.restore(&snapshot);
+        assert_eq!(context.eval_number("rand()").unwrap(), roll);
+    }
+
+    #[test]
+    fn caches_steady_lines() {
+        let mut context = EvalContext::new();
+        context.cache(2);
+        context.set("n", Value::number(100.0));
+        assert_eq!(context.eval_number("sum(i, 1, n, i)").unwrap(), 5050.0);
+        assert_eq!(context.cache.values.len(), 1);
+        assert_eq!(context.eval_number("sum(i,1,n,i)").unwrap(), 5050.0);
+        assert_eq!(context.cache.order.len(), 1);
+        assert_eq!(context.eval_number("$1 + $2").unwrap(), 10100.0);
+        context.set("n", Value::number(10.0));
+        assert_eq!(context.eval_number("sum(i, 1, n, i)").unwrap(), 55.0);
+        context.eval_line("seed(1)").unwrap();
+        let roll = context.eval_number("rand()").unwrap();
+        assert_ne!(context.eval_number("rand()").unwrap(), roll);
+        assert!(context.eval_line("1/0").is_err());
+        assert!(context.eval_line("1/0").is_err());
+        assert_eq!(context.eval_number("2^10").unwrap(), 1024.0);
+        assert_eq!(context.cache.values.len(), 2);
+        context.clear_cache();
+        assert!(context.cache.values.is_empty());
+    }
+
+    #[test]
+    fn completions_follow_names() {
+        let mut context = EvalContext::new();
+        let completions = context.calc.completions();
+        context.set("radius", Value::number(3.0));
+        assert!(completions.borrow().iter().any(|n| n == "radius"));
+        context.eval_line(":clear radius").unwrap();
+        assert!(!completions.borrow().iter().any(|n| n == "radius"));
+    }
+
+    #[test]
+    fn reports_errors_with_their_line() {
+        let mut context = EvalContext::new();
+        let error = context.eval_str("1\n2\n1/\n").unwrap_err();
+        let CalcError::Syntax { at, .. } = error else {
+            panic!("expected a syntax error, not {:?}", error);
+        };
+        assert_eq!(at.line, 3);
+        assert_eq!(context.eval_str("1\n2+3\n").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn series_have_a_limit() {
+        assert_eq!(eval("sum(i, 1, 100, i)").unwrap(), 5050.0);
+        assert!(eval("sum(i, 1, 10^18, i)").is_err());
+        assert!(eval("prod(i, -10^30, 10^30, 1)").is_err());
+    }
+
+    #[test]
+    fn diff_checks_unit_exponents() {
+        let mut context = EvalContext::new();
+        let error = context.eval_line("diff(x^-128, x, 1 m)").unwrap_err();
+        assert!(error.to_string().contains("unit exponent out of range"));
+    }
+
+    #[test]
+    fn variables_shadow_units() {
+        let mut context = EvalContext::new();
+        assert_eq!(
+            context.eval_line("2 m").unwrap().unwrap().to_string(),
+            "2 m"
+        );
+        assert_eq!(eval("sum(m, 1, 4, m)").unwrap(), 10.0);
+        assert_eq!(eval("diff(s^3, s, 2)").unwrap().round(), 12.0);
+        context.set("t", Value::number(3.0));
+        assert_eq!(context.eval_number("t*2 + t^2").unwrap(), 15.0);
+    }
+
+    #[test]
+    fn reports_invalid_dates() {
+        let mut context = EvalContext::new();
+        assert_eq!(
+            context.eval_number("2024-03-01 - 2024-02-01").unwrap(),
+            29.0
+        );
+        let error = context.eval_line("2024-02-30").unwrap_err();
+        assert_eq!(error.to_string(), "invalid date 2024-02-30");
+    }
+
+    #[test]
+    fn each_context_has_its_own_random_numbers() {
+        let (mut a, mut b) = (EvalContext::new(), EvalContext::new());
+        a.eval_line("seed(42)").unwrap();
+        let first = a.eval_number("randint(1, 1000000)").unwrap();
+        b.eval_line("seed(42)").unwrap();
+        a.eval_line("seed(42)").unwrap();
+        b.eval_number("rand()").unwrap();
+        assert_eq!(a.eval_number("randint(1, 1000000)").unwrap(), first);
+    }
+
+    #[test]
+    fn eval_needs_a_plain_number() {
+        assert_eq!(eval("2^10").unwrap(), 1024.0);
+        assert!(eval("2 m").is_err());
+    }
+}
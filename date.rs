@@ -0,0 +1,164 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds in a day
+pub const DAY: f64 = 86400.0;
+
+/// How many years either side of 1970 a date may be
+const YEARS: i64 = 1_000_000;
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Year, month and day for a count of days since 1970-01-01
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (
+        if m <= 2 {
+            yoe + era * 400 + 1
+        } else {
+            yoe + era * 400
+        },
+        m,
+        d,
+    )
+}
+
+/// Midnight UTC on the given day, if there is such a day
+pub fn from_ymd(y: i64, m: i64, d: i64) -> Option<f64> {
+    if y.abs() > YEARS || !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    let days = days_from_civil(y, m, d);
+    if civil_from_days(days) != (y, m, d) {
+        return None;
+    }
+    Some(days as f64 * DAY)
+}
+
+/// 't', unless it is too far from 1970 to show as a date
+pub fn check(t: f64) -> Result<f64, String> {
+    if t.is_finite() && t.abs() <= YEARS as f64 * 365.0 * DAY {
+        Ok(t)
+    } else {
+        Err(String::from("date out of range"))
+    }
+}
+
+/// Parse "YYYY-MM-DD", optionally followed by " HH:MM" or " HH:MM:SS"
+pub fn parse(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (day, time) = match text.split_once([' ', 'T']) {
+        Some((day, time)) => (day, Some(time)),
+        None => (text, None),
+    };
+    let mut ymd = day.splitn(3, '-').map(|s| s.parse::<i64>().ok());
+    let date = from_ymd(ymd.next()??, ymd.next()??, ymd.next()??)?;
+    let Some(time) = time else {
+        return Some(date);
+    };
+    let mut hms = time.split(':').map(|s| s.parse::<u32>().ok());
+    let h = hms.next()??;
+    let m = hms.next()??;
+    let s = hms.next().unwrap_or(Some(0))?;
+    if h > 23 || m > 59 || s > 59 || hms.next().is_some() {
+        return None;
+    }
+    Some(date + (h * 3600 + m * 60 + s) as f64)
+}
+
+/// The current time
+pub fn now() -> f64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as f64,
+        Err(e) => -e.duration().as_secs_f64().ceil(),
+    }
+}
+
+/// Midnight at the start of the current day
+pub fn today() -> f64 {
+    (now() / DAY).floor() * DAY
+}
+
+/// Display a time as "2024-06-01", adding the time of day when
+/// it isn't midnight
+pub struct Date(pub f64);
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Round to the second first, so a time just before midnight
+        // carries into the next day rather than showing as 24:00
+        let t = self.0.round();
+        let days = (t / DAY).floor();
+        let (y, m, d) = civil_from_days(days as i64);
+        write!(f, "{:04}-{:02}-{:02}", y, m, d)?;
+        let secs = (t - days * DAY) as i64;
+        if secs != 0 {
+            write!(f, " {:02}:{:02}", secs / 3600, secs / 60 % 60)?;
+            if secs % 60 != 0 {
+                write!(f, ":{:02}", secs % 60)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_the_calendar() {
+        assert_eq!(from_ymd(1970, 1, 2), Some(DAY));
+        assert_eq!(from_ymd(2024, 2, 29), Some(19782.0 * DAY));
+        assert_eq!(from_ymd(2023, 2, 29), None);
+        assert_eq!(from_ymd(2024, 13, 1), None);
+        assert_eq!(from_ymd(2024, 4, 31), None);
+    }
+
+    #[test]
+    fn parses_times_of_day() {
+        assert_eq!(parse("1970-01-01 01:02:03"), Some(3723.0));
+        assert_eq!(parse("1970-01-01T00:30"), Some(1800.0));
+        assert_eq!(parse("1970-01-01 24:00"), None);
+    }
+
+    #[test]
+    fn rounds_into_the_next_day() {
+        assert_eq!(Date(DAY - 0.4).to_string(), "1970-01-02");
+        assert_eq!(Date(DAY - 1.6).to_string(), "1970-01-01 23:59:58");
+        assert_eq!(Date(-DAY).to_string(), "1969-12-31");
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::generate::Generator;
+use crate::ll1::Table;
+use crate::random::{self, Rng};
+use crate::value::Value;
+use crate::{evaluate, EAction, ENonTerminal, ETerminal};
+use std::io::Write;
+use std::panic;
+use std::process::{Command, Stdio};
+
+/// How deep to nest before heading for the shortest derivation
+const DEPTH: usize = 8;
+
+/// Run the reference command over all of the expressions at once
+fn reference(command: &str, exprs: &[String]) -> Result<Vec<String>, String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: {}", command, e))?;
+    let mut stdin = child.stdin.take().ok_or("cannot write to reference")?;
+    let input = exprs.join("\n") + "\n";
+    // Write from another thread so a chatty reference can't block us
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("{}: {}", command, e))?;
+    let _ = writer.join();
+    let results: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect();
+    if results.len() != exprs.len() {
+        return Err(format!(
+            "reference gave {} results for {} expressions",
+            results.len(),
+            exprs.len()
+        ));
+    }
+    Ok(results)
+}
+
+/// Evaluate 'count' random expressions made from the parse table,
+/// comparing with the reference command if there is one. That reads the
+/// expressions one per line and writes one result per line, anything
+/// other than a number counting as an error, e.g.
+///
+/// ```sh
+/// calc --difftest 1000 --reference 'python3 -c "
+/// import sys
+/// for l in sys.stdin:
+///     try: print(float(eval(l)))
+///     except Exception: print(\"error\")"'
+/// ```
+///
+/// Returns the number of panics and mismatches.
+pub fn run(
+    table: &Table<ETerminal, ENonTerminal, EAction>,
+    count: usize,
+    command: Option<&str>,
+) -> Result<usize, String> {
+    let generator = Generator::new(table);
+    let mut rng = Rng::new(random::clock());
+    let exprs: Vec<String> = (0..count)
+        .map(|_| generator.generate(DEPTH, rng.next()))
+        .collect();
+    let expected = match command {
+        Some(command) => Some(reference(command, &exprs)?),
+        None => None,
+    };
+    // Panics are reported below with their expressions
+    panic::set_hook(Box::new(|_| {}));
+    let (mut errors, mut problems) = (0, 0);
+    for (i, expr) in exprs.iter().enumerate() {
+        let got = match evaluate(&format!("{}\n", expr)) {
+            Ok(Value::Scalar(q)) if q.val.is_finite() => Ok(q.val),
+            Ok(v) => Err(format!("not a finite number: {}", v)),
+            Err(message) => Err(message),
+        };
+        if let Err(message) = &got {
+            errors += 1;
+            if message.starts_with("panic") {
+                problems += 1;
+                println!("{}: {}", expr, message);
+                continue;
+            }
+        }
+        let Some(expected) = &expected else {
+            continue;
+        };
+        let want = expected[i].trim().parse::<f64>().ok();
+        let same = match (&got, want) {
+            (Ok(a), Some(b)) => (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0),
+            (Err(_), None) => true,
+            _ => false,
+        };
+        if !same {
+            problems += 1;
+            match &got {
+                Ok(a) => println!("{}: calc {}, reference {}", expr, a, expected[i]),
+                Err(m) => println!("{}: calc {}, reference {}", expr, m, expected[i]),
+            }
+        }
+    }
+    let _ = panic::take_hook();
+    println!(
+        "{} expressions, {} errors, {} problems",
+        count, errors, problems
+    );
+    Ok(problems)
+}
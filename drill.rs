@@ -0,0 +1,95 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::random::{self, Rng};
+use crate::units::NONE;
+use crate::value::Value;
+use crate::Calc;
+use crate::ENonTerminal::Line;
+use std::io::Write;
+
+/// Make up a problem for the given level, returning the text and the
+/// answer. Level 1 adds and subtracts small numbers, level 2 adds
+/// multiplication, level 3 division and higher levels bigger numbers.
+fn problem(rng: &mut Rng, level: u32) -> (String, i128) {
+    let level = level as i128;
+    let ops = match level {
+        1 => 2,
+        2 => 3,
+        _ => 4,
+    };
+    let size = 10 * level * level;
+    let factor = 3 * level + 6;
+    match rng.range(0, ops - 1) {
+        0 => {
+            let (a, b) = (rng.range(1, size), rng.range(1, size));
+            (format!("{} + {}", a, b), a + b)
+        }
+        1 => {
+            let (a, b) = (rng.range(1, size), rng.range(1, size));
+            let (a, b) = (a.max(b), a.min(b));
+            (format!("{} - {}", a, b), a - b)
+        }
+        2 => {
+            let (a, b) = (rng.range(2, factor), rng.range(2, factor));
+            (format!("{} * {}", a, b), a * b)
+        }
+        _ => {
+            // Pick the answer first so the division comes out even
+            let (a, b) = (rng.range(2, factor), rng.range(2, factor));
+            (format!("{} / {}", a * b, b), a)
+        }
+    }
+}
+
+fn is(v: &Value, answer: i128) -> bool {
+    match v {
+        Value::Scalar(q) => q.dim == NONE && q.val == answer as f64,
+        _ => false,
+    }
+}
+
+/// Ask problems until the input runs out, keeping score
+pub fn run(calc: &mut Calc, level: u32) {
+    println!(
+        "Level {} drill. Answer each problem; press ctrl-D to stop.",
+        level
+    );
+    let mut rng = Rng::new(random::clock());
+    let (mut right, mut asked) = (0, 0);
+    loop {
+        let (text, answer) = problem(&mut rng, level);
+        println!();
+        println!("What is {}?", text);
+        print!("> ");
+        let _ = std::io::stdout().flush();
+        let results = calc.results;
+        if !calc.parse(Line) {
+            println!();
+            break;
+        }
+        asked += 1;
+        if calc.results > results && is(&calc.ans, answer) {
+            right += 1;
+            println!("Correct! Score {} of {}", right, asked);
+        } else {
+            println!("No, {} = {}. Score {} of {}", text, answer, right, asked);
+        }
+    }
+    println!("Final score {} of {}", right, asked);
+}
@@ -0,0 +1,421 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use calc::{CONTINUED, INTERRUPTED};
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use std::sync::atomic::Ordering::Relaxed;
+
+/// Switch the terminal out of line mode so keys arrive as they're
+/// pressed, restoring it when dropped. The termios layout and flag
+/// values are those of Linux on these architectures; others, like
+/// powerpc, mips and sparc, lay it out differently.
+#[cfg(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv32",
+        target_arch = "riscv64",
+        target_arch = "loongarch64"
+    )
+))]
+mod raw {
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Termios {
+        iflag: u32,
+        oflag: u32,
+        cflag: u32,
+        lflag: u32,
+        line: u8,
+        cc: [u8; 32],
+        ispeed: u32,
+        ospeed: u32,
+    }
+
+    extern "C" {
+        fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+        fn tcsetattr(fd: i32, action: i32, termios: *const Termios) -> i32;
+    }
+
+    const ISIG: u32 = 0o1;
+    const ICANON: u32 = 0o2;
+    const ECHO: u32 = 0o10;
+    const VTIME: usize = 5;
+    const VMIN: usize = 6;
+    const TCSANOW: i32 = 0;
+
+    pub struct Raw(Termios);
+
+    impl Raw {
+        pub fn new() -> Option<Raw> {
+            let mut saved = Termios {
+                iflag: 0,
+                oflag: 0,
+                cflag: 0,
+                lflag: 0,
+                line: 0,
+                cc: [0; 32],
+                ispeed: 0,
+                ospeed: 0,
+            };
+            // SAFETY: 'saved' is a termios struct for tcgetattr to fill in
+            if unsafe { tcgetattr(0, &mut saved) } != 0 {
+                return None;
+            }
+            let mut raw = saved;
+            // Ctrl-C arrives as a key, to drop the line being typed
+            raw.lflag &= !(ICANON | ECHO | ISIG);
+            raw.cc[VMIN] = 1;
+            raw.cc[VTIME] = 0;
+            // SAFETY: 'raw' is the struct tcgetattr filled in, modified
+            if unsafe { tcsetattr(0, TCSANOW, &raw) } != 0 {
+                return None;
+            }
+            Some(Raw(saved))
+        }
+    }
+
+    impl Drop for Raw {
+        fn drop(&mut self) {
+            // SAFETY: restoring the settings tcgetattr returned
+            unsafe { tcsetattr(0, TCSANOW, &self.0) };
+        }
+    }
+}
+
+/// Elsewhere, leave the terminal alone and read whole lines
+#[cfg(not(all(
+    target_os = "linux",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "riscv32",
+        target_arch = "riscv64",
+        target_arch = "loongarch64"
+    )
+)))]
+mod raw {
+    pub struct Raw;
+
+    impl Raw {
+        pub fn new() -> Option<Raw> {
+            None
+        }
+    }
+}
+
+/// Make Ctrl-C abandon the line being evaluated rather than the
+/// session, by setting INTERRUPTED for the calculator to notice
+#[cfg(unix)]
+pub fn catch_interrupts() {
+    extern "C" fn interrupt(_signal: i32) {
+        INTERRUPTED.store(true, Relaxed);
+    }
+    extern "C" {
+        fn signal(signal: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+    const SIGINT: i32 = 2;
+    // SAFETY: the handler only stores to an atomic
+    unsafe { signal(SIGINT, interrupt) };
+}
+
+#[cfg(not(unix))]
+pub fn catch_interrupts() {}
+
+const PROMPT: &str = "> ";
+
+/// For a line continuing the expression from the one before
+const CONTINUATION: &str = ". ";
+
+/// Keys the editor acts on
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    KillEnd,
+    KillStart,
+    Complete,
+    Interrupt,
+    Eof,
+    Ignore,
+}
+
+/// Interactive input with a prompt, cursor movement, editing and
+/// history, handed to the lexer a line at a time
+pub struct Editor {
+    history: Vec<String>,
+
+    // What Tab completes, sorted, as the calculator keeps it
+    names: Rc<RefCell<Vec<String>>>,
+
+    // The accepted line, waiting to be read
+    pending: Vec<u8>,
+    offset: usize,
+
+    done: bool,
+}
+
+impl Editor {
+    pub fn new(names: Rc<RefCell<Vec<String>>>) -> Editor {
+        Editor {
+            history: Vec::new(),
+            names,
+            pending: Vec::new(),
+            offset: 0,
+            done: false,
+        }
+    }
+
+    fn byte(&self) -> Option<u8> {
+        let mut c = [0u8; 1];
+        match io::stdin().read(&mut c) {
+            Ok(1) => Some(c[0]),
+            _ => None,
+        }
+    }
+
+    /// Read one key press, decoding UTF-8 and escape sequences
+    fn key(&self) -> Key {
+        let Some(b) = self.byte() else {
+            return Key::Eof;
+        };
+        match b {
+            b'\n' | b'\r' => Key::Enter,
+            0x7f | 0x08 => Key::Backspace,
+            0x01 => Key::Home,
+            0x05 => Key::End,
+            0x0b => Key::KillEnd,
+            0x15 => Key::KillStart,
+            0x03 => Key::Interrupt,
+            b'\t' => Key::Complete,
+            0x04 => Key::Eof,
+            0x1b => {
+                if self.byte() != Some(b'[') {
+                    return Key::Ignore;
+                }
+                match self.byte() {
+                    Some(b'A') => Key::Up,
+                    Some(b'B') => Key::Down,
+                    Some(b'C') => Key::Right,
+                    Some(b'D') => Key::Left,
+                    Some(b'H') => Key::Home,
+                    Some(b'F') => Key::End,
+                    Some(b'3') if self.byte() == Some(b'~') => Key::Delete,
+                    _ => Key::Ignore,
+                }
+            }
+            b if b < 0x20 => Key::Ignore,
+            b if b < 0x80 => Key::Char(b as char),
+            b => {
+                // Gather the rest of a multi-byte character
+                let len = b.leading_ones() as usize;
+                let mut bytes = vec![b];
+                for _ in 1..len.clamp(1, 4) {
+                    bytes.extend(self.byte());
+                }
+                match std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                {
+                    Some(c) => Key::Char(c),
+                    None => Key::Ignore,
+                }
+            }
+        }
+    }
+
+    /// Rewrite the line and put the cursor back where it belongs
+    fn redraw(prompt: &str, line: &[char], cursor: usize) {
+        let text: String = line.iter().collect();
+        print!("\r{}{}\x1b[K", prompt, text);
+        if cursor < line.len() {
+            print!("\x1b[{}D", line.len() - cursor);
+        }
+        let _ = io::stdout().flush();
+    }
+
+    /// Complete the name before the cursor as far as the names
+    /// starting that way agree, listing them when that adds nothing
+    fn complete(&self, line: &mut Vec<char>, cursor: &mut usize) {
+        let mut start = *cursor;
+        while start > 0 && line[start - 1].is_ascii_alphanumeric() {
+            start -= 1;
+        }
+        // Commands keep their ':'
+        if start > 0 && line[start - 1] == ':' {
+            start -= 1;
+        }
+        if start == *cursor {
+            return;
+        }
+        let prefix: String = line[start..*cursor].iter().collect();
+        let names = self.names.borrow();
+        let matches: Vec<&String> = names
+            .iter()
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        let Some(first) = matches.first() else {
+            return;
+        };
+        // The longest start they all share; names are ASCII, as the
+        // lexer reads them
+        let mut common = first.as_str();
+        for name in &matches[1..] {
+            while !name.starts_with(common) {
+                common = &common[..common.len() - 1];
+            }
+        }
+        if common.len() > prefix.len() {
+            for c in common[prefix.len()..].chars() {
+                line.insert(*cursor, c);
+                *cursor += 1;
+            }
+        } else if matches.len() > 1 {
+            println!();
+            let names: Vec<&str> = matches.iter().map(|name| name.as_str()).collect();
+            println!("{}", names.join("  "));
+        }
+    }
+
+    /// Edit a line, returning None at the end of input
+    fn edit(&mut self) -> Option<String> {
+        // A Ctrl-C that came after the last line finished is for
+        // this one
+        INTERRUPTED.store(false, Relaxed);
+        let prompt = match CONTINUED.swap(false, Relaxed) {
+            true => CONTINUATION,
+            false => PROMPT,
+        };
+        let Some(_raw) = raw::Raw::new() else {
+            print!("{}", prompt);
+            let _ = io::stdout().flush();
+            let mut line = String::new();
+            return match io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => None,
+                // Ctrl-C while typing drops the line
+                Ok(_) if INTERRUPTED.swap(false, Relaxed) => Some(String::new()),
+                Ok(_) => Some(String::from(line.trim_end_matches('\n'))),
+            };
+        };
+        let mut line: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        // Position in history, which is the line being typed at the end
+        let mut entry = self.history.len();
+        let mut scratch: Vec<char> = Vec::new();
+        Editor::redraw(prompt, &line, cursor);
+        loop {
+            match self.key() {
+                Key::Char(c) => {
+                    line.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => break,
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                Key::Delete if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                Key::Left if cursor > 0 => cursor -= 1,
+                Key::Right if cursor < line.len() => cursor += 1,
+                Key::Home => cursor = 0,
+                Key::End => cursor = line.len(),
+                Key::KillEnd => line.truncate(cursor),
+                Key::KillStart => {
+                    line.drain(..cursor);
+                    cursor = 0;
+                }
+                Key::Up if entry > 0 => {
+                    if entry == self.history.len() {
+                        scratch = line.clone();
+                    }
+                    entry -= 1;
+                    line = self.history[entry].chars().collect();
+                    cursor = line.len();
+                }
+                Key::Down if entry < self.history.len() => {
+                    entry += 1;
+                    line = match self.history.get(entry) {
+                        Some(text) => text.chars().collect(),
+                        None => scratch.clone(),
+                    };
+                    cursor = line.len();
+                }
+                Key::Complete => self.complete(&mut line, &mut cursor),
+                Key::Interrupt => {
+                    println!("^C");
+                    line.clear();
+                    cursor = 0;
+                    entry = self.history.len();
+                }
+                Key::Eof if line.is_empty() => {
+                    println!();
+                    return None;
+                }
+                _ => {}
+            }
+            Editor::redraw(prompt, &line, cursor);
+        }
+        println!();
+        let text: String = line.into_iter().collect();
+        if !text.trim().is_empty() && self.history.last() != Some(&text) {
+            self.history.push(text.clone());
+        }
+        Some(text)
+    }
+}
+
+impl Read for Editor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset == self.pending.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.edit() {
+                Some(text) => {
+                    self.pending = text.into_bytes();
+                    self.pending.push(b'\n');
+                }
+                None => {
+                    self.done = true;
+                    return Ok(0);
+                }
+            }
+            self.offset = 0;
+        }
+        let n = buf.len().min(self.pending.len() - self.offset);
+        buf[..n].copy_from_slice(&self.pending[self.offset..self.offset + n]);
+        self.offset += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,120 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::ETerminal;
+use std::error::Error;
+use std::fmt;
+
+/// Where something is in the input, counting lines and columns from
+/// one and bytes from zero
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+/// What went wrong with a line, and where
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CalcError {
+    /// A token the grammar doesn't allow there, and those it would
+    Syntax {
+        at: Position,
+        found: ETerminal,
+        expected: Vec<ETerminal>,
+    },
+    /// Input which isn't any token
+    Lex { at: Position, message: String },
+    /// Input which ended inside parentheses, opened at 'at'
+    Unclosed { at: Position },
+    /// A line that parsed, but couldn't be evaluated
+    Eval { at: Position, message: String },
+}
+
+impl CalcError {
+    pub fn position(&self) -> Position {
+        match self {
+            CalcError::Syntax { at, .. }
+            | CalcError::Lex { at, .. }
+            | CalcError::Unclosed { at }
+            | CalcError::Eval { at, .. } => *at,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.position().line
+    }
+
+    pub fn column(&self) -> usize {
+        self.position().column
+    }
+
+    pub fn offset(&self) -> usize {
+        self.position().offset
+    }
+
+    /// The same error, in line 'number' of some longer text, where
+    /// that line starts 'start' bytes in
+    pub fn on_line(mut self, number: usize, start: usize) -> CalcError {
+        match &mut self {
+            CalcError::Syntax { at, .. }
+            | CalcError::Lex { at, .. }
+            | CalcError::Unclosed { at }
+            | CalcError::Eval { at, .. } => {
+                at.line = number;
+                at.offset += start;
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalcError::Syntax {
+                found, expected, ..
+            } => {
+                write!(f, "syntax error: unexpected {}", found)?;
+                for (i, t) in expected.iter().enumerate() {
+                    let sep = match i {
+                        0 => ", expected",
+                        _ if i + 1 == expected.len() => " or",
+                        _ => ",",
+                    };
+                    write!(f, "{} {}", sep, t)?;
+                }
+                Ok(())
+            }
+            CalcError::Unclosed { at } => write!(f, "unclosed '(' opened at {}", at),
+            CalcError::Lex { message, .. } | CalcError::Eval { message, .. } => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl Error for CalcError {}
@@ -0,0 +1,110 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::units::NONE;
+use crate::value::Value;
+use crate::EvalContext;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// A calculator as C and C++ programs see it, through calc.h, holding
+/// on to the latest error so calc_error can hand out a pointer to it
+pub struct Calc {
+    context: EvalContext,
+    error: Option<CString>,
+}
+
+/// Make a calculator, to be released with calc_free
+#[no_mangle]
+pub extern "C" fn calc_new() -> *mut Calc {
+    Box::into_raw(Box::new(Calc {
+        context: EvalContext::new(),
+        error: None,
+    }))
+}
+
+/// Release a calculator from calc_new
+///
+/// # Safety
+///
+/// 'calc' must be NULL or come from calc_new, and not be used again
+#[no_mangle]
+pub unsafe extern "C" fn calc_free(calc: *mut Calc) {
+    if !calc.is_null() {
+        // SAFETY: the caller passes a pointer from calc_new, once
+        drop(unsafe { Box::from_raw(calc) });
+    }
+}
+
+/// Evaluate a line, storing its value, which must be a plain number, in
+/// 'result'. Returns 0, or 1 for a line with no value like a command,
+/// or -1 with the error left for calc_error.
+///
+/// # Safety
+///
+/// 'calc' must come from calc_new, 'input' must be a NUL-terminated
+/// string and 'result' must point to a double
+#[no_mangle]
+pub unsafe extern "C" fn calc_eval(
+    calc: *mut Calc,
+    input: *const c_char,
+    result: *mut f64,
+) -> c_int {
+    // SAFETY: the caller passes a pointer from calc_new
+    let Some(calc) = (unsafe { calc.as_mut() }) else {
+        return -1;
+    };
+    if input.is_null() || result.is_null() {
+        calc.error = CString::new("calc_eval needs input and a result").ok();
+        return -1;
+    }
+    // SAFETY: the caller passes a NUL-terminated string
+    let input = unsafe { CStr::from_ptr(input) }.to_string_lossy();
+    calc.error = None;
+    // Panics mustn't unwind into C
+    let value = panic::catch_unwind(AssertUnwindSafe(|| calc.context.eval_line(&input)));
+    let message = match value {
+        Ok(Ok(None)) => return 1,
+        Ok(Ok(Some(Value::Scalar(q)))) if q.dim == NONE => {
+            // SAFETY: the caller passes a pointer to a double
+            unsafe { *result = q.val };
+            return 0;
+        }
+        Ok(Ok(Some(value))) => format!("expected a plain number, not {}", value),
+        Ok(Err(error)) => error.to_string(),
+        Err(_) => String::from("internal error"),
+    };
+    calc.error = CString::new(message.replace('\0', "")).ok();
+    -1
+}
+
+/// The latest error from calc_eval, or NULL if it succeeded. The text
+/// lasts until the next calc_eval or calc_free.
+///
+/// # Safety
+///
+/// 'calc' must come from calc_new
+#[no_mangle]
+pub unsafe extern "C" fn calc_error(calc: *const Calc) -> *const c_char {
+    // SAFETY: the caller passes a pointer from calc_new
+    match unsafe { calc.as_ref() }.and_then(|calc| calc.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
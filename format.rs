@@ -0,0 +1,244 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::radix;
+use crate::units::Quantity;
+use std::fmt::{self, Write};
+
+/// How numbers are written out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Notation {
+    /// As short as will read back the same
+    General,
+    /// With this many places after the point
+    Fixed(usize),
+    /// One digit before the point and a power of ten
+    Sci,
+    /// Powers of a thousand, written with SI prefixes
+    Eng,
+}
+
+/// Digits enough to print any f64 exactly as it is
+pub const PRECISION: usize = 17;
+
+/// How a session writes numbers out, which ':format', ':precision',
+/// ':outbase' and ':separator' change
+#[derive(Clone, Debug, PartialEq)]
+pub struct Style {
+    pub notation: Notation,
+    /// Significant digits
+    pub digits: usize,
+    /// Radixes other than ten are written the way radix::format does,
+    /// whatever the notation
+    pub radix: u32,
+    /// What goes between groups of three digits in general and fixed
+    /// notation; empty leaves them ungrouped
+    pub separator: String,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            notation: Notation::General,
+            digits: PRECISION,
+            radix: 10,
+            separator: String::new(),
+        }
+    }
+}
+
+/// Longest an f64 can be written, as with the 309 digits of f64::MAX
+/// or the 300-odd zeros after the point of the smallest subnormal
+const LONGEST: usize = 512;
+
+/// Text written into an array, so numbers can be rounded and grouped
+/// without going through the heap
+struct Buffer {
+    bytes: [u8; LONGEST],
+    len: usize,
+}
+
+impl Buffer {
+    fn new() -> Buffer {
+        Buffer {
+            bytes: [0; LONGEST],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+impl fmt::Write for Buffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.bytes
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Write the text with the separator between each group of three
+/// digits before the point
+fn group(f: &mut fmt::Formatter, text: &str, separator: &str) -> fmt::Result {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text),
+    };
+    let (whole, fraction) = digits.split_at(digits.find('.').unwrap_or(digits.len()));
+    if separator.is_empty() || whole.len() <= 3 {
+        return f.write_str(text);
+    }
+    f.write_str(sign)?;
+    for (i, c) in whole.char_indices() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            f.write_str(separator)?;
+        }
+        write!(f, "{}", c)?;
+    }
+    f.write_str(fraction)
+}
+
+/// SI prefixes for powers of a thousand, starting at 10^-24
+const PREFIXES: [&str; 17] = [
+    "y", "z", "a", "f", "p", "n", "u", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+];
+
+/// Split '{:e}' output into its digits, with the point, and exponent
+fn exponent(x: f64) -> (String, i32) {
+    let s = format!("{:e}", x);
+    match s.split_once('e') {
+        Some((mantissa, exp)) => (String::from(mantissa), exp.parse().unwrap_or(0)),
+        None => (s, 0),
+    }
+}
+
+/// Move the decimal point in 'mantissa' right by 'by' places
+fn shift(mantissa: &str, by: usize) -> String {
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", mantissa),
+    };
+    let mut digits: String = digits.chars().filter(|c| *c != '.').collect();
+    while digits.len() < by + 1 {
+        digits.push('0');
+    }
+    let (whole, fraction) = digits.split_at(by + 1);
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Write 'x' in the style's notation, rounded to the formatter's
+/// precision, or else the style's, taken as significant digits
+pub fn number(f: &mut fmt::Formatter, x: f64, style: &Style) -> fmt::Result {
+    if !x.is_finite() {
+        return write!(f, "{}", x);
+    }
+    if style.radix != 10 {
+        if let Ok(s) = radix::format(&Quantity::new(x), style.radix) {
+            return write!(f, "{}", s);
+        }
+    }
+    let digits = f.precision().unwrap_or(style.digits);
+    let mut text = Buffer::new();
+    write!(text, "{:.*e}", digits.max(1) - 1, x)?;
+    let rounded: f64 = text.as_str().parse().unwrap_or(x);
+    let separator = &style.separator;
+    text.len = 0;
+    match style.notation {
+        Notation::General => {
+            write!(text, "{}", rounded)?;
+            group(f, text.as_str(), separator)
+        }
+        Notation::Fixed(places) => {
+            write!(text, "{:.*}", places, x)?;
+            group(f, text.as_str(), separator)
+        }
+        Notation::Sci => write!(f, "{:e}", rounded),
+        Notation::Eng => {
+            if rounded == 0.0 {
+                return write!(f, "0");
+            }
+            let (mantissa, exp) = exponent(rounded);
+            let by = exp.rem_euclid(3);
+            let exp = exp - by;
+            let mantissa = shift(&mantissa, by as usize);
+            match PREFIXES.get((exp / 3 + 8) as usize) {
+                Some(prefix) if (-24..=24).contains(&exp) => write!(f, "{}{}", mantissa, prefix),
+                _ => write!(f, "{}e{}", mantissa, exp),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Notation::General => write!(f, "general"),
+            Notation::Fixed(places) => write!(f, "fixed {}", places),
+            Notation::Sci => write!(f, "sci"),
+            Notation::Eng => write!(f, "eng"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    fn show(x: f64, notation: Notation) -> String {
+        let style = Style {
+            notation,
+            ..Style::default()
+        };
+        Value::number(x).styled(&style).to_string()
+    }
+
+    #[test]
+    fn writes_each_notation() {
+        assert_eq!(show(1234.5, Notation::General), "1234.5");
+        assert_eq!(show(1.005, Notation::Fixed(1)), "1.0");
+        assert_eq!(show(-9.876, Notation::Fixed(2)), "-9.88");
+        assert_eq!(show(12345.0, Notation::Sci), "1.2345e4");
+        assert_eq!(show(0.0123, Notation::Eng), "12.3m");
+        assert_eq!(show(4.7e30, Notation::Eng), "4.7e30");
+        assert_eq!(show(0.0, Notation::Eng), "0");
+    }
+
+    #[test]
+    fn writes_other_radixes_whatever_the_notation() {
+        let style = Style {
+            notation: Notation::Sci,
+            radix: 16,
+            ..Style::default()
+        };
+        assert_eq!(Value::number(255.0).styled(&style).to_string(), "0xff");
+        assert_eq!(Value::number(-2.5).styled(&style).to_string(), "-0x2.8");
+        let style = Style { radix: 2, ..style };
+        assert_eq!(Value::number(5.0).styled(&style).to_string(), "0b101");
+    }
+}
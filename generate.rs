@@ -0,0 +1,137 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::ll1::Table;
+use crate::ll1::Token::*;
+use crate::random::Rng;
+use crate::ETerminal::{self, *};
+use crate::Token;
+use crate::{EAction, ENonTerminal};
+use std::collections::HashMap;
+
+/// Terminals other evaluators understand, spelled their way
+fn spell(terminal: ETerminal, rng: &mut Rng) -> Option<String> {
+    let text = match terminal {
+        NUMBER => return Some(rng.range(0, 99).to_string()),
+        PLUS => "+",
+        MINUS => "-",
+        TIMES => "*",
+        DIVIDE => "/",
+        OP => "(",
+        CP => ")",
+        _ => return None,
+    };
+    Some(String::from(text))
+}
+
+/// Makes random expressions from the productions of a parse table which
+/// use only plain arithmetic, so that other evaluators can read them too
+pub struct Generator {
+    productions: HashMap<ENonTerminal, Vec<Vec<Token>>>,
+
+    // Height of the shortest derivation from each non-terminal
+    height: HashMap<ENonTerminal, usize>,
+}
+
+impl Generator {
+    pub fn new(table: &Table<ETerminal, ENonTerminal, EAction>) -> Generator {
+        let mut rng = Rng::new(0);
+        let mut entries: Vec<(ENonTerminal, &[Token])> = table
+            .entries()
+            .filter(|(_, _, rhs)| {
+                rhs.iter().all(|t| match t {
+                    Terminal(t) => spell(*t, &mut rng).is_some(),
+                    _ => true,
+                })
+            })
+            .map(|(_, nt, rhs)| (nt, rhs))
+            .collect();
+        // Sort so that a given seed always makes the same expression
+        entries.sort_by_key(|e| format!("{:?}", e));
+        entries.dedup();
+        let mut productions: HashMap<ENonTerminal, Vec<Vec<Token>>> = HashMap::new();
+        for (nt, rhs) in entries {
+            productions.entry(nt).or_default().push(rhs.to_vec());
+        }
+        let mut generator = Generator {
+            productions,
+            height: HashMap::new(),
+        };
+        loop {
+            let mut changed = false;
+            for (nt, rhss) in &generator.productions {
+                let best = rhss.iter().map(|rhs| generator.cost(rhs)).min();
+                if let Some(best) = best.filter(|b| *b < generator.height(*nt)) {
+                    generator.height.insert(*nt, best);
+                    changed = true;
+                }
+            }
+            if !changed {
+                return generator;
+            }
+        }
+    }
+
+    fn height(&self, nt: ENonTerminal) -> usize {
+        *self.height.get(&nt).unwrap_or(&usize::MAX)
+    }
+
+    /// Height of the shortest derivation using 'rhs'
+    fn cost(&self, rhs: &[Token]) -> usize {
+        rhs.iter()
+            .map(|t| match t {
+                NonTerminal(nt) => self.height(*nt),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+            .saturating_add(1)
+    }
+
+    /// Pick productions at random until 'depth' non-terminals deep,
+    /// then take the shortest way out
+    fn derive(&self, nt: ENonTerminal, depth: usize, rng: &mut Rng, words: &mut Vec<String>) {
+        let Some(rhss) = self.productions.get(&nt) else {
+            return;
+        };
+        let rhs = if depth > 0 {
+            &rhss[rng.range(0, rhss.len() as i128 - 1) as usize]
+        } else {
+            match rhss.iter().min_by_key(|rhs| self.cost(rhs)) {
+                Some(rhs) => rhs,
+                None => return,
+            }
+        };
+        for token in rhs {
+            match token {
+                Terminal(t) => words.extend(spell(*t, rng)),
+                NonTerminal(nt) => self.derive(*nt, depth.saturating_sub(1), rng, words),
+                Action(_) => {}
+            }
+        }
+    }
+
+    /// A random expression nesting about 'depth' non-terminals deep;
+    /// the same seed always gives the same expression
+    pub fn generate(&self, depth: usize, seed: u64) -> String {
+        let mut rng = Rng::new(seed);
+        let mut words = Vec::new();
+        self.derive(ENonTerminal::Expr, depth, &mut rng, &mut words);
+        words.join(" ")
+    }
+}
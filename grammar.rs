@@ -0,0 +1,89 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::ll1::{self, Symbol, Table, Token};
+use crate::{EAction, ENonTerminal, ETerminal};
+
+const ACTIONS: &[EAction] = {
+    use EAction::*;
+    &[
+        Negate, Add, Subtract, Times, Divide, Push, Print, Mark, Array, Begin, Call, Ans, Percent,
+        MemAdd, MemSub, MemClear, Recall, Cond, Then, Else, Power, Variable, Unit, Name, Body,
+        Hold, Held, Apply, Next, Approx, Command,
+    ]
+};
+
+/// The token a grammar file means by 'name', which is spelled as in
+/// the source
+fn symbol(name: &str) -> Option<Token<ETerminal, ENonTerminal, EAction>> {
+    let named = |debug: String| debug == name;
+    if let Some(t) = ETerminal::ALL.iter().find(|t| named(format!("{:?}", t))) {
+        return Some(Token::Terminal(*t));
+    }
+    if let Some(n) = ENonTerminal::ALL.iter().find(|n| named(format!("{:?}", n))) {
+        return Some(Token::NonTerminal(*n));
+    }
+    ACTIONS
+        .iter()
+        .find(|a| named(format!("{:?}", a)))
+        .map(|a| Token::Action(*a))
+}
+
+/// Build a parse table from a grammar description, one non-terminal
+/// per line as in
+///
+/// ```text
+/// ExprP -> PLUS Term Add ExprP | MINUS Term Subtract ExprP | ε
+/// ```
+///
+/// Terminals, non-terminals and actions are named as in the source,
+/// so a grammar can rearrange the language but not add to it. The
+/// first line is for the start symbol; '#' starts a comment. An
+/// alternative starting with '!' wins any conflict with the others.
+pub fn load(text: &str) -> Result<Table<ETerminal, ENonTerminal, EAction>, String> {
+    let mut productions = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let (lhs, rhs) = line
+            .split_once("->")
+            .ok_or_else(|| error(String::from("expected '->'")))?;
+        let lhs = match symbol(lhs.trim()) {
+            Some(Token::NonTerminal(n)) => n,
+            _ => return Err(error(format!("{} is not a non-terminal", lhs.trim()))),
+        };
+        for alternative in rhs.split('|') {
+            let (alternative, preferred) = match alternative.trim().strip_prefix('!') {
+                Some(alternative) => (alternative, true),
+                None => (alternative, false),
+            };
+            let mut tokens = Vec::new();
+            for name in alternative.split_whitespace() {
+                if name == "ε" {
+                    continue;
+                }
+                tokens.push(symbol(name).ok_or_else(|| error(format!("unknown symbol {}", name)))?);
+            }
+            productions.push((lhs, tokens, preferred));
+        }
+    }
+    ll1::build(&productions, ETerminal::END).map(Table::from_entries)
+}
@@ -0,0 +1,2253 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, Cursor, Read};
+use std::mem;
+use std::panic;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::time::Instant;
+
+pub mod ast;
+mod banner;
+mod builtins;
+mod compile;
+mod context;
+mod date;
+mod difftest;
+pub mod drill;
+mod error;
+pub mod ffi;
+pub mod format;
+mod generate;
+mod grammar;
+pub mod ll1;
+mod loops;
+mod primes;
+mod radix;
+mod random;
+mod rational;
+pub mod reduce;
+pub mod sink;
+mod speak;
+mod symbolic;
+pub mod tutorial;
+mod units;
+pub mod value;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+use builtins::{Callee, Registered, BUILTINS};
+pub use compile::Compiled;
+pub use context::{eval, EvalContext};
+pub use error::{CalcError, Position};
+use format::{Notation, Style, PRECISION};
+use ll1::{Driver, Error, Machine, Recovery, Symbol};
+use loops::{Diff, Kind, Series, Solve, Step};
+use random::Rng;
+pub use rational::Rational;
+use sink::{Input, ResultSink, Silent, Stdout};
+use units::Quantity;
+use value::Value;
+
+/// What ':help' says before listing the functions
+const HELP: &str = "\
+operators: + - * / ^, and % for percent, as in 200 + 15%
+comparison: a ~= b is 1 when a and b are nearly equal, else 0
+grouping: ( ), and [a, b] for vectors, [[a, b], [c, d]] for matrices
+choice: if(condition, then, else)
+series: sum(i, lo, hi, body) and prod(i, lo, hi, body)
+calculus: diff(body, x, point) and solve(body, x, start)
+memory: M+ x, M- x, MC and MR
+history: $1 is the latest result, $2 the one before, and so on
+symbols: after ':symbolic on', unknown names stay as they are, as in 2*x + 3*x
+lines: ';' separates expressions on one line
+";
+
+/// The ':' commands
+const COMMANDS: &[&str] = &[
+    "clear",
+    "format",
+    "help",
+    "outbase",
+    "precision",
+    "reset",
+    "separator",
+    "source",
+    "stats",
+    "symbolic",
+    "tol",
+    "trace",
+    "vars",
+];
+
+/// Words which start an expression like a function call
+const KEYWORDS: &[&str] = &["diff", "if", "prod", "solve", "sum"];
+
+/// How many results '$1', '$2' and so on reach back through
+const HISTORY: usize = 100;
+
+/// Relative and absolute tolerance for '~=' to start with
+const TOLERANCE: (f64, f64) = (1e-9, 1e-12);
+
+/// Features a front end might look for, printed by --capabilities.
+/// Those behind Cargo features say whether this build has them.
+pub const CAPABILITIES: &[(&str, bool)] = &[
+    ("bigint", false),
+    ("dates", true),
+    ("matrices", true),
+    ("plotting", false),
+    ("rational", true),
+    ("serde", cfg!(feature = "serde")),
+    ("server", false),
+    ("speak", true),
+    ("tracing", cfg!(feature = "tracing")),
+    ("units", true),
+    ("wasm", cfg!(feature = "wasm")),
+];
+
+/// Set to abandon the line being evaluated, as the binary does on
+/// Ctrl-C
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C has asked to abandon the line, clearing the request.
+/// Evaluation checks before each action, and builtins which may loop
+/// for a long time check as they go.
+pub(crate) fn interrupted() -> Result<(), String> {
+    match INTERRUPTED.swap(false, Relaxed) {
+        true => Err(String::from("interrupted")),
+        false => Ok(()),
+    }
+}
+
+/// Set when a newline didn't end the expression, so that the line read
+/// next continues it, for a prompt to show
+pub static CONTINUED: AtomicBool = AtomicBool::new(false);
+
+trait MakeToken {
+    fn make_token(self) -> Token;
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ETerminal {
+    NONE,
+    OP,
+    CP,
+    OB,
+    CB,
+    COMMA,
+    NUMBER,
+    DATE,
+    STRING,
+    UNIT,
+    MADD,
+    MSUB,
+    MCLEAR,
+    MRECALL,
+    COMMAND,
+    IF,
+    NAME,
+    SERIES,
+    APPLY,
+    FUNCTION,
+    PLUS,
+    MINUS,
+    TIMES,
+    DIVIDE,
+    POW,
+    PERCENT,
+    APPROX,
+    NL,
+    END,
+    RESET,
+}
+use ETerminal::*;
+
+impl ETerminal {
+    /// Whether this needs something after it, as an operator does
+    fn is_operator(self) -> bool {
+        matches!(self, PLUS | MINUS | TIMES | DIVIDE | POW | APPROX)
+    }
+}
+
+/// Terminals as error messages describe them
+impl fmt::Display for ETerminal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            NONE => "nothing",
+            OP => "'('",
+            CP => "')'",
+            OB => "'['",
+            CB => "']'",
+            COMMA => "','",
+            NUMBER => "a number",
+            DATE => "a date",
+            STRING => "text",
+            UNIT => "a unit",
+            MADD => "'M+'",
+            MSUB => "'M-'",
+            MCLEAR => "'MC'",
+            MRECALL => "'MR'",
+            COMMAND => "a command",
+            IF => "'if'",
+            NAME => "a name",
+            SERIES => "'sum' or 'prod'",
+            APPLY => "'diff' or 'solve'",
+            FUNCTION => "a function",
+            PLUS => "'+'",
+            MINUS => "'-'",
+            TIMES => "'*'",
+            DIVIDE => "'/'",
+            POW => "'^'",
+            PERCENT => "'%'",
+            APPROX => "'~='",
+            NL => "end of line",
+            END => "end of input",
+            RESET => "invalid input",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Ignore the number's value for hash and eq
+impl Hash for ETerminal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state)
+    }
+}
+
+impl PartialEq for ETerminal {
+    fn eq(&self, other: &Self) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+    }
+}
+
+impl Eq for ETerminal {}
+
+impl Symbol for ETerminal {
+    const ALL: &'static [ETerminal] = &[
+        NONE, OP, CP, OB, CB, COMMA, NUMBER, DATE, STRING, UNIT, MADD, MSUB, MCLEAR, MRECALL,
+        COMMAND, IF, NAME, SERIES, APPLY, FUNCTION, PLUS, MINUS, TIMES, DIVIDE, POW, PERCENT,
+        APPROX, NL, END, RESET,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(PartialEq, Hash, Eq, Clone, Copy, Debug)]
+pub enum ENonTerminal {
+    Start,
+    Expr,
+    ExprP,
+    Term,
+    TermP,
+    Fact,
+    Units,
+    List,
+    ListP,
+    Line,
+    Pct,
+    Memory,
+    PowP,
+    Exp,
+    SeriesArgs,
+    Rel,
+    Words,
+    Var,
+}
+use ENonTerminal::*;
+
+impl Symbol for ENonTerminal {
+    const ALL: &'static [ENonTerminal] = &[
+        Start, Expr, ExprP, Term, TermP, Fact, Units, List, ListP, Line, Pct, Memory, PowP, Exp,
+        SeriesArgs, Rel, Words, Var,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(PartialEq, Hash, Eq, Clone, Copy, Debug)]
+pub enum EAction {
+    Negate,
+    Add,
+    Subtract,
+    Times,
+    Divide,
+    Push,
+    Print,
+    Mark,
+    Array,
+    Begin,
+    Call,
+    Ans,
+    Percent,
+    MemAdd,
+    MemSub,
+    MemClear,
+    Recall,
+    Cond,
+    Then,
+    Else,
+    Power,
+    Variable,
+    Unit,
+    Name,
+    Body,
+    Hold,
+    Held,
+    Apply,
+    Next,
+    Approx,
+    Command,
+}
+use EAction::*;
+
+type Token = ll1::Token<ETerminal, ENonTerminal, EAction>;
+use ll1::Token::{Action, NonTerminal, Terminal};
+
+/// Convert an ETerminal into a Token for token_vec!
+impl MakeToken for ETerminal {
+    fn make_token(self) -> Token {
+        Terminal(self)
+    }
+}
+
+/// Convert an ENonTerminal into a Token for token_vec!
+impl MakeToken for ENonTerminal {
+    fn make_token(self) -> Token {
+        NonTerminal(self)
+    }
+}
+
+/// Convert an EAction into a Token for token_vec!
+impl MakeToken for EAction {
+    fn make_token(self) -> Token {
+        Action(self)
+    }
+}
+
+/// Convert each argument into a token for easy immediates
+macro_rules! token_vec {
+    () => { Vec::new() };
+    ( $( $x:expr ), + ) => {
+        vec![$( $x.make_token() ),*]
+    };
+}
+
+/// Lexer state, kept between tokens. As an iterator, it yields each
+/// token up to the end of the input.
+pub struct Lexer<R = Box<dyn Read>> {
+    // Where the characters come from, read a block at a time
+    input: BufReader<R>,
+
+    // Lex state to avoid needing ungetc
+    c: char,
+
+    // Most recent identifier
+    name: String,
+
+    // Digits of the number being read
+    digits: String,
+
+    /// Accept 'x' and ':' for numeric keypad entry
+    pub keypad: bool,
+
+    // Names of functions added by a program, lexed like builtins
+    functions: HashSet<String>,
+
+    // Tokens read ahead while looking for a date, last first
+    pending: Vec<(ETerminal, Value)>,
+
+    // Text of the line being read, to report along with its result,
+    // its line number and the file it's from
+    line: String,
+    number: usize,
+    file: Option<String>,
+
+    // Bytes read so far, and where the latest token started
+    offset: usize,
+    position: Position,
+
+    // Whether the input has run out
+    ended: bool,
+
+    // Parentheses and brackets opened on the line and not yet closed,
+    // and the latest token; the line carries on past a newline until
+    // they're closed, and after an operator
+    open: usize,
+    last: ETerminal,
+
+    // Set to end the line at the next newline regardless
+    ending: bool,
+}
+
+impl<R: Read> Lexer<R> {
+    pub fn new(input: R) -> Lexer<R> {
+        Lexer {
+            input: BufReader::new(input),
+            c: '\0',
+            name: String::new(),
+            digits: String::new(),
+            keypad: false,
+            functions: HashSet::new(),
+            pending: Vec::new(),
+            line: String::new(),
+            number: 0,
+            file: None,
+            offset: 0,
+            position: Position::default(),
+            ended: false,
+            open: 0,
+            last: NONE,
+            ending: false,
+        }
+    }
+
+    /// The line read so far, without its newline
+    fn input(&self) -> Input<'_> {
+        Input {
+            file: self.file.as_deref(),
+            number: self.number,
+            text: self.line.trim_end_matches(['\r', '\n']),
+            column: None,
+        }
+    }
+
+    /// Where the latest token started
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Whether the input has run out, though the line it ended in may
+    /// not have been parsed yet
+    pub fn ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Read a single character, decoding UTF-8, returning '\0' on EOF
+    fn getc(&mut self) -> char {
+        let mut bytes = [0u8; 4];
+        let _ = self.input.read(&mut bytes[..1]);
+        if bytes[0] == 0 {
+            self.ended = true;
+            return '\0';
+        }
+        let len = match bytes[0] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        let c = match self.input.read_exact(&mut bytes[1..len]) {
+            Ok(()) => std::str::from_utf8(&bytes[..len])
+                .ok()
+                .and_then(|s| s.chars().next()),
+            Err(_) => None,
+        }
+        .unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.offset += len;
+        if self.line.is_empty() || self.line.ends_with('\n') {
+            self.line.clear();
+            self.number += 1;
+        }
+        self.line.push(c);
+        c
+    }
+
+    /// Read decimal digits, returning the value and how many there were
+    fn digits(&mut self) -> (i128, usize) {
+        let mut val: i128 = 0;
+        let mut count = 0;
+        while let Some(digit) = self.c.to_digit(10) {
+            val = val.saturating_mul(10).saturating_add(digit as i128);
+            count += 1;
+            self.c = self.getc();
+        }
+        (val, count)
+    }
+
+    /// Having read a four digit year and seeing '-', try for the rest
+    /// of a YYYY-MM-DD date. Anything else is left as subtraction, as
+    /// in '2024-1'.
+    fn date(&mut self, year: i128) -> (ETerminal, Value) {
+        let number = |n: i128| Value::Scalar(Quantity::rational(Rational::integer(n)));
+        self.c = self.getc();
+        let (month, count) = self.digits();
+        if count == 0 {
+            self.pending.push((MINUS, Value::number(0.0)));
+            return (NUMBER, number(year));
+        }
+        if count != 2 || self.c != '-' {
+            self.pending.push((NUMBER, number(month)));
+            self.pending.push((MINUS, Value::number(0.0)));
+            return (NUMBER, number(year));
+        }
+        self.c = self.getc();
+        let (day, count) = self.digits();
+        if count == 2 {
+            let year = i64::try_from(year).unwrap_or(i64::MAX);
+            if let Some(t) = date::from_ymd(year, month as i64, day as i64) {
+                return (DATE, Value::Date(t));
+            }
+            return (
+                RESET,
+                Value::Text(format!("invalid date {}-{:02}-{:02}", year, month, day)),
+            );
+        }
+        if count != 0 {
+            self.pending.push((NUMBER, number(day)));
+        }
+        self.pending.push((MINUS, Value::number(0.0)));
+        self.pending.push((NUMBER, number(month)));
+        self.pending.push((MINUS, Value::number(0.0)));
+        (NUMBER, number(year))
+    }
+
+    /// Read one token, keeping track of whether the line can end after it
+    fn lex(&mut self) -> (ETerminal, Value) {
+        let token = self.token();
+        match token.0 {
+            OP | OB => self.open += 1,
+            CP | CB => self.open = self.open.saturating_sub(1),
+            NL | END => {
+                self.open = 0;
+                self.ending = false;
+            }
+            _ => {}
+        }
+        self.last = token.0;
+        token
+    }
+
+    /// End the line at the next newline, whatever is left open, as
+    /// when skipping past an error
+    fn end_line(&mut self) {
+        self.ending = true;
+    }
+
+    /// Read one token, leaving identifiers in 'name'. In keypad mode, 'x'
+    /// multiplies and ':' divides.
+    fn token(&mut self) -> (ETerminal, Value) {
+        if let Some(token) = self.pending.pop() {
+            return token;
+        }
+        let mut val: f64 = 0.0;
+        let mut exact: Option<i128> = Some(0);
+        if self.c == '\0' {
+            self.c = self.getc();
+        }
+        loop {
+            // The end of input is just past the last character; anything
+            // else has been read already
+            let read = (self.c != '\0') as usize;
+            self.position = Position {
+                line: self.number,
+                column: self.line.chars().count() + 1 - read,
+                offset: self.offset - read,
+            };
+            let terminal = match self.c {
+                // Including no-break and other spaces pasted from documents
+                c0 if c0.is_whitespace() && c0 != '\n' && c0 != '\r' => {
+                    self.c = self.getc();
+                    continue;
+                }
+                // A last line without a newline still ends there
+                '\0' if !self.line.is_empty() && !self.line.ends_with('\n') => {
+                    self.line.push('\n');
+                    NL
+                }
+                '\0' => END,
+                // Inside parentheses or after an operator, the expression
+                // carries on in the next line
+                '\n' if !self.ending && (self.open > 0 || self.last.is_operator()) => {
+                    CONTINUED.store(true, Relaxed);
+                    self.c = self.getc();
+                    continue;
+                }
+                '\n' => NL,
+                // Lines from Windows end in "\r\n"
+                '\r' => {
+                    self.c = self.getc();
+                    if self.c != '\n' {
+                        return (RESET, Value::Text(unknown('\r')));
+                    }
+                    continue;
+                }
+                c0 if c0.is_ascii_digit() => {
+                    // 0x, 0o and 0b prefixes select another radix, except
+                    // in keypad mode where '0x' is zero times something
+                    let mut radix = 10;
+                    let mut count = 0;
+                    if self.c == '0' && !self.keypad {
+                        self.c = self.getc();
+                        count += 1;
+                        radix = match self.c {
+                            'x' => 16,
+                            'o' => 8,
+                            'b' => 2,
+                            _ => 10,
+                        };
+                        if radix != 10 {
+                            self.c = self.getc();
+                            if self.c.to_digit(radix).is_none() {
+                                return (RESET, Value::number(val));
+                            }
+                        }
+                    }
+                    self.digits.clear();
+                    if count > 0 {
+                        self.digits.push('0');
+                    }
+                    while let Some(digit) = self.c.to_digit(radix) {
+                        exact = exact
+                            .and_then(|e| e.checked_mul(radix as i128))
+                            .and_then(|e| e.checked_add(digit as i128));
+                        self.digits.push(self.c);
+                        count += 1;
+                        self.c = self.getc();
+                    }
+                    // Rounded once, to the nearest f64, rather than at
+                    // each digit
+                    val = match exact {
+                        Some(e) => e as f64,
+                        None if radix == 10 => self.digits.parse().unwrap_or(f64::INFINITY),
+                        None => self.digits.chars().fold(0.0, |v, c| {
+                            v * radix as f64 + c.to_digit(radix).unwrap_or(0) as f64
+                        }),
+                    };
+                    if radix == 10 && count == 4 && self.c == '-' {
+                        if let Some(year) = exact {
+                            return self.date(year);
+                        }
+                    }
+                    let exact = exact.map(Rational::integer);
+                    return (
+                        NUMBER,
+                        Value::Scalar(Quantity {
+                            exact,
+                            ..Quantity::new(val)
+                        }),
+                    );
+                }
+                c0 if c0.is_ascii_alphabetic() => {
+                    self.name.clear();
+                    // A lone 'x' is multiplication, as in '2x3', but 'x'
+                    // starting a longer name is left alone
+                    if self.keypad && self.c == 'x' {
+                        self.c = self.getc();
+                        if !self.c.is_ascii_alphabetic() {
+                            return (TIMES, Value::number(val));
+                        }
+                        self.name.push('x');
+                    }
+                    while self.c.is_ascii_alphanumeric() {
+                        self.name.push(self.c);
+                        self.c = self.getc();
+                    }
+                    while self.c == ' ' || self.c == '\t' {
+                        self.c = self.getc();
+                    }
+                    // Desk calculator memory keys
+                    match (self.name.as_str(), self.c) {
+                        ("M", '+') | ("M", '-') => {
+                            let terminal = if self.c == '+' { MADD } else { MSUB };
+                            self.c = '\0';
+                            return (terminal, Value::number(val));
+                        }
+                        ("MC", _) => return (MCLEAR, Value::number(val)),
+                        ("MR", _) => return (MRECALL, Value::number(val)),
+                        _ => {}
+                    }
+                    if self.c == '(' {
+                        match self.name.as_str() {
+                            "if" => return (IF, Value::number(val)),
+                            "sum" | "prod" => return (SERIES, Value::Text(self.name.clone())),
+                            "diff" | "solve" => return (APPLY, Value::Text(self.name.clone())),
+                            _ => {}
+                        }
+                    }
+                    // A name followed by '(' is a function call, then units,
+                    // and anything else is a variable
+                    if self.c == '('
+                        && (builtins::find(&self.name).is_some()
+                            || self.functions.contains(&self.name))
+                    {
+                        return (FUNCTION, Value::Text(self.name.clone()));
+                    }
+                    return match Quantity::unit(&self.name) {
+                        Some(unit) => (UNIT, Value::Scalar(unit)),
+                        None => (NAME, Value::Text(self.name.clone())),
+                    };
+                }
+                '"' => {
+                    // Text runs to the closing quote on the same line
+                    let mut text = String::new();
+                    self.c = self.getc();
+                    while self.c != '"' {
+                        if self.c == '\n' || self.c == '\0' {
+                            return (RESET, Value::number(val));
+                        }
+                        text.push(self.c);
+                        self.c = self.getc();
+                    }
+                    self.c = '\0';
+                    return (STRING, Value::Text(text));
+                }
+                '+' => PLUS,
+                '-' => MINUS,
+                '*' => TIMES,
+                '/' => DIVIDE,
+                '^' => POW,
+                '%' => PERCENT,
+                // Earlier results, as in '$1' for the latest
+                '$' => {
+                    self.name.clear();
+                    self.name.push('$');
+                    self.c = self.getc();
+                    while self.c.is_ascii_digit() {
+                        self.name.push(self.c);
+                        self.c = self.getc();
+                    }
+                    if self.name.len() == 1 {
+                        return (RESET, Value::Text(unknown('$')));
+                    }
+                    return (NAME, Value::Text(self.name.clone()));
+                }
+                ':' if self.keypad => DIVIDE,
+                ':' => {
+                    // Settings commands, as in ':tol'
+                    let mut name = String::new();
+                    self.c = self.getc();
+                    while self.c.is_ascii_alphabetic() {
+                        name.push(self.c);
+                        self.c = self.getc();
+                    }
+                    return (COMMAND, Value::Text(name));
+                }
+                '~' => {
+                    self.c = self.getc();
+                    if self.c != '=' {
+                        return (RESET, Value::number(val));
+                    }
+                    APPROX
+                }
+                '(' => OP,
+                ')' => CP,
+                '[' => OB,
+                ']' => CB,
+                ',' => COMMA,
+                // Separates expressions on one line, as a newline would
+                ';' => NL,
+                c0 => {
+                    self.c = '\0';
+                    return (RESET, Value::Text(unknown(c0)));
+                }
+            };
+            self.c = '\0';
+            return (terminal, Value::number(val));
+        }
+    }
+}
+
+/// An error message for a character which doesn't start any token,
+/// naming those likely to be pasted in from documents
+fn unknown(c: char) -> String {
+    let name = match c {
+        '\u{d7}' => "multiplication sign",
+        '\u{f7}' => "division sign",
+        '\u{2212}' => "minus sign",
+        '\u{2013}' => "en dash",
+        '\u{2014}' => "em dash",
+        '\u{2018}' | '\u{2019}' => "single quotation mark",
+        '\u{201c}' | '\u{201d}' => "double quotation mark",
+        '\u{3c0}' => "Greek small letter pi",
+        '\u{b5}' => "micro sign",
+        '\u{b0}' => "degree sign",
+        '\r' => "carriage return",
+        char::REPLACEMENT_CHARACTER => "invalid UTF-8",
+        _ => "",
+    };
+    let code = match name {
+        "" => format!("U+{:04X}", c as u32),
+        name => format!("U+{:04X} {}", c as u32, name),
+    };
+    if c.is_control() || c.is_whitespace() || c == char::REPLACEMENT_CHARACTER {
+        format!("unknown character {}", code)
+    } else {
+        format!("unknown character '{}' ({})", c, code)
+    }
+}
+
+/// Input that doesn't make a token, and the line it's on
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on line {}", self.message, self.line)
+    }
+}
+
+impl<R: Read> Iterator for Lexer<R> {
+    type Item = Result<(ETerminal, Value), LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lex() {
+            (END, _) => None,
+            (RESET, value) => Some(Err(LexError {
+                line: self.number,
+                message: match value {
+                    Value::Text(message) => message,
+                    _ => String::from("invalid input"),
+                },
+            })),
+            token => Some(Ok(token)),
+        }
+    }
+}
+
+/// A syntax error at the end of the last line is at the end of the
+/// input, when that line had no newline
+fn at_end(error: Error<ETerminal>, ended: bool) -> Error<ETerminal> {
+    match error {
+        Error::Syntax(NL, expected) if ended => Error::Syntax(END, expected),
+        error => error,
+    }
+}
+
+/// An error from parsing, placed at 'at', where 'value' is the
+/// lookahead's, which says what was wrong with input the lexer
+/// couldn't make a token from, and 'opened' is where the innermost
+/// '(' yet to be closed was
+fn place(
+    error: Error<ETerminal>,
+    at: Position,
+    value: &Value,
+    opened: Option<Position>,
+) -> CalcError {
+    match error {
+        Error::Syntax(END, _) if opened.is_some() => CalcError::Unclosed {
+            at: opened.unwrap_or(at),
+        },
+        Error::Syntax(RESET, _) if matches!(value, Value::Text(_)) => CalcError::Lex {
+            at,
+            message: value.to_string(),
+        },
+        Error::Syntax(found, mut expected) => {
+            expected.sort_by_key(|t| *t as usize);
+            CalcError::Syntax {
+                at,
+                found,
+                expected,
+            }
+        }
+        Error::Action(message) => CalcError::Eval { at, message },
+    }
+}
+
+/// The name of the unit just read: replayed tokens carry it as their
+/// value, and otherwise it's the last name the lexer read
+fn unit_name<'a, R>(value: &'a Value, lexer: &'a Lexer<R>) -> &'a str {
+    match value {
+        Value::Text(name) => name,
+        _ => &lexer.name,
+    }
+}
+
+/// When the stacks don't hold what the parse table promised, as with
+/// a grammar file whose actions don't fit together. That's an error
+/// for the line rather than a crash.
+fn internal(what: &str) -> String {
+    format!("internal error: {}", what)
+}
+
+/// Add 'epop' and 'esplit_off' methods to Vec to trap stack underflow
+trait EPop<T> {
+    fn epop(self) -> Result<T, String>;
+    fn esplit_off(self, at: usize) -> Result<Vec<T>, String>;
+}
+
+impl<T> EPop<T> for &mut Vec<T> {
+    fn epop(self) -> Result<T, String> {
+        self.pop().ok_or_else(|| internal("stack underflow"))
+    }
+
+    fn esplit_off(self, at: usize) -> Result<Vec<T>, String> {
+        if at > self.len() {
+            return Err(internal("stack underflow"));
+        }
+        Ok(self.split_off(at))
+    }
+}
+
+/// A sum, prod or diff in progress. The body's tokens are recorded as
+/// it is parsed, and replayed for each further value of the variable.
+struct Loop {
+    name: String,
+
+    // Value of the variable for this pass over the body
+    val: Value,
+
+    // None until the variable and its starting point are known
+    kind: Option<Kind>,
+
+    tokens: Vec<(ETerminal, Value, Position)>,
+    recording: bool,
+
+    // The answer, when known without evaluating the body at all
+    result: Option<Value>,
+
+    // False when the loop is inside an untaken 'if' branch
+    live: bool,
+}
+
+impl Loop {
+    fn new() -> Loop {
+        Loop {
+            name: String::new(),
+            val: Value::number(0.0),
+            kind: None,
+            tokens: Vec::new(),
+            recording: false,
+            result: None,
+            live: false,
+        }
+    }
+}
+
+// What each non-terminal expands to for each lookahead, as build.rs
+// makes it from calc.grammar
+include!(concat!(env!("OUT_DIR"), "/table.rs"));
+
+fn table() -> ll1::Table<ETerminal, ENonTerminal, EAction> {
+    ll1::Table::new(&TABLE)
+}
+
+/// The modes a session can change as it goes, which ':reset' puts
+/// back the way they were when the session started
+#[derive(Clone)]
+struct Modes {
+    style: Style,
+    tolerance: (f64, f64),
+    trace: bool,
+    symbolic: bool,
+    strict: bool,
+    warn: bool,
+    timings: bool,
+    keypad: bool,
+}
+
+/// What a session has built up between lines: the names a program
+/// gave it, its results and memory, its modes and where its random
+/// numbers have got to. EvalContext::snapshot takes one and restore
+/// puts it back.
+#[derive(Clone)]
+pub struct Snapshot {
+    ans: Value,
+    history: VecDeque<Value>,
+    memory: Value,
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, Registered>,
+    rng: Rng,
+    modes: Modes,
+}
+
+/// Parser and evaluator state, kept between lines. Parsing and
+/// evaluation go together: actions in the parse table evaluate as
+/// each part of an expression is matched, so Calc is both the Parser
+/// and the Evaluator. For the parse alone, ast::parse builds trees
+/// with the same table; Lexer reads the tokens for either.
+pub struct Calc {
+    // Parse table and stack
+    driver: Driver<ETerminal, ENonTerminal, EAction>,
+
+    // Value stack
+    values: Vec<Value>,
+
+    // Most recently printed result
+    ans: Value,
+
+    // Results printed lately, most recent first, for '$1', '$2' and so on
+    history: VecDeque<Value>,
+
+    pub lexer: Lexer,
+    lexeme: ETerminal,
+    value: Value,
+
+    // Where the lookahead is
+    position: Position,
+
+    // Where each operator yet to be applied was, with the depth of the
+    // value stack when it was read
+    operators: Vec<(usize, Position)>,
+
+    // Where the latest action which failed went wrong
+    failed: Option<Position>,
+
+    // Where each '(' yet to be closed was
+    opened: Vec<Position>,
+
+    // Desk calculator memory register
+    memory: Value,
+
+    // Value stack depth at the start of each array or argument list
+    marks: Vec<usize>,
+
+    // Whether the top value was just written as a percentage
+    percent: bool,
+
+    // For each 'if' being parsed, whether the condition held, or
+    // None when the whole 'if' is being skipped
+    conditions: Vec<Option<bool>>,
+
+    // Nesting of untaken 'if' branches; actions are skipped while set
+    skip: usize,
+
+    // Sums and products being evaluated, innermost last
+    loops: Vec<Loop>,
+
+    // Tokens to read again before more input, each with one more than
+    // the index of the loop replaying it
+    replay: Vec<(ETerminal, Value, Position, usize)>,
+
+    // Functions awaiting their arguments
+    calls: Vec<Callee>,
+
+    // Functions added by a program embedding the calculator
+    functions: HashMap<String, Registered>,
+
+    // Values given names by a program embedding the calculator
+    variables: HashMap<String, Value>,
+
+    // names(), kept up to date for an editor to complete from
+    completions: Rc<RefCell<Vec<String>>>,
+
+    // What rand, randint and seed draw from
+    rng: Rng,
+
+    // Relative and absolute tolerance for '~='
+    tolerance: (f64, f64),
+
+    // What ':reset' goes back to, from keep_modes
+    start: Option<Modes>,
+
+    // Number of results printed and errors reported so far
+    results: usize,
+    pub errors: usize,
+
+    // Most recent error reported
+    last_error: Option<CalcError>,
+
+    // Files being read by ':source', innermost last
+    sources: Vec<String>,
+
+    // Errors on a line which parsing carried on past, to report once
+    // the whole line has been read
+    unreported: Vec<CalcError>,
+
+    // Deepest the parse and value stacks have been, for ':stats'
+    peak_stack: usize,
+    peak_values: usize,
+
+    // Evaluate without printing results or errors
+    quiet: bool,
+
+    // Stop at the first error
+    pub strict: bool,
+
+    // Point out results which overflowed or aren't numbers
+    pub warn: bool,
+
+    // Print the lookahead, parse stack and value stack at each step,
+    // on stderr, apart from the results
+    pub trace: bool,
+
+    // Keep unknown names as symbols rather than reporting them
+    pub symbolic: bool,
+
+    // How numbers are written out
+    pub style: Style,
+
+    // Print command output in words for screen readers
+    pub speak_style: bool,
+
+    // Where results and errors are reported
+    pub sink: Box<dyn ResultSink>,
+
+    // Follow each result with how long its line took, timed from
+    // the line's first token
+    pub timings: bool,
+    started: Option<Instant>,
+}
+
+impl Calc {
+    pub fn new(input: Box<dyn Read>) -> Calc {
+        let mut calc = Calc {
+            driver: Driver::new(table()),
+            values: Vec::new(),
+            ans: Value::number(0.0),
+            history: VecDeque::with_capacity(HISTORY),
+            memory: Value::number(0.0),
+            lexer: Lexer::new(input),
+            lexeme: NONE,
+            value: Value::number(0.0),
+            position: Position::default(),
+            operators: Vec::new(),
+            failed: None,
+            opened: Vec::new(),
+            marks: Vec::new(),
+            percent: false,
+            conditions: Vec::new(),
+            skip: 0,
+            loops: Vec::new(),
+            replay: Vec::new(),
+            calls: Vec::new(),
+            functions: HashMap::new(),
+            variables: HashMap::new(),
+            completions: Rc::default(),
+            rng: Rng::new(random::clock()),
+            tolerance: TOLERANCE,
+            start: None,
+            results: 0,
+            errors: 0,
+            last_error: None,
+            sources: Vec::new(),
+            unreported: Vec::new(),
+            peak_stack: 0,
+            peak_values: 0,
+            quiet: false,
+            strict: false,
+            warn: true,
+            trace: false,
+            symbolic: false,
+            style: Style::default(),
+            speak_style: false,
+            sink: Box::new(Stdout {
+                speak_style: false,
+                banner: false,
+            }),
+            timings: false,
+            started: None,
+        };
+        calc.keep_modes();
+        calc.renamed();
+        calc
+    }
+
+    /// Fetch the next token. Loops record what they read on their first
+    /// pass, but not the tokens replayed by loops within them.
+    fn lex(&mut self) {
+        let source;
+        (self.lexeme, self.value, self.position, source) = match self.replay.pop() {
+            Some(token) => token,
+            None => {
+                let (lexeme, value) = self.lexer.lex();
+                if lexeme != NL && self.started.is_none() {
+                    self.started = Some(Instant::now());
+                }
+                (lexeme, value, self.lexer.position(), 0)
+            }
+        };
+        // Replayed units need their names, in case a variable has one
+        if self.lexeme == UNIT && self.loops.iter().any(|l| l.recording) {
+            self.value = Value::Text(String::from(unit_name(&self.value, &self.lexer)));
+        }
+        for (depth, l) in self.loops.iter_mut().enumerate() {
+            if l.recording && source <= depth {
+                l.tokens
+                    .push((self.lexeme, self.value.clone(), self.position));
+            }
+        }
+    }
+
+    /// Go round 'l' again: replay its body followed by the current
+    /// lookahead, tagged so that loops outside it don't record them twice
+    fn again(&mut self, l: Loop) {
+        let tag = self.loops.len() + 1;
+        let lookahead = mem::replace(&mut self.value, Value::number(0.0));
+        self.replay
+            .push((self.lexeme, lookahead, self.position, tag));
+        for (lexeme, value, position) in l.tokens.iter().rev() {
+            self.replay.push((*lexeme, value.clone(), *position, tag));
+        }
+        self.lexeme = NONE;
+        self.driver.stack.push(Action(Next));
+        self.driver.stack.push(NonTerminal(Expr));
+        self.loops.push(l);
+    }
+
+    /// Read from 'input' from now on, naming it 'file' in errors
+    pub fn read(&mut self, file: Option<&str>, input: Box<dyn Read>) {
+        let mut lexer = Lexer::new(input);
+        lexer.keypad = self.lexer.keypad;
+        lexer.functions = mem::take(&mut self.lexer.functions);
+        lexer.file = file.map(String::from);
+        self.lexer = lexer;
+        self.lexeme = NONE;
+    }
+
+    /// Evaluate each line of a file
+    pub fn file(&mut self, path: &str) -> Result<(), String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.read(Some(path), Box::new(file));
+        self.parse(Start);
+        Ok(())
+    }
+
+    /// Evaluate each line of a file in the middle of another's, as
+    /// ':source' does, then carry on with the line after the command
+    fn source(&mut self, path: &str) -> Result<(), String> {
+        if self.sources.iter().any(|p| p == path) {
+            return Err(format!("{} is already being read", path));
+        }
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let lexer = mem::replace(&mut self.lexer, Lexer::new(Box::new(io::empty())));
+        let stack = mem::take(&mut self.driver.stack);
+        let (lexeme, value, position) = (self.lexeme, self.value.clone(), self.position);
+        let errors = self.errors;
+        self.sources.push(String::from(path));
+        self.read(Some(path), Box::new(file));
+        self.lexer.keypad = lexer.keypad;
+        self.parse(Start);
+        self.sources.pop();
+        let functions = mem::take(&mut self.lexer.functions);
+        self.lexer = lexer;
+        self.lexer.functions = functions;
+        self.driver.stack = stack;
+        (self.lexeme, self.value, self.position) = (lexeme, value, position);
+        // Fail the command too, so that --strict stops at it
+        match self.errors - errors {
+            0 => Ok(()),
+            1 => Err(format!("{} had an error", path)),
+            n => Err(format!("{} had {} errors", path, n)),
+        }
+    }
+
+    /// Parse with the grammar described in a file, as grammar::load
+    /// reads them, from now on
+    pub fn grammar(&mut self, path: &str) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.driver.table = grammar::load(&text).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(())
+    }
+
+    /// Evaluate the startup file without showing results, leaving its
+    /// errors out of the count
+    pub fn startup(&mut self, path: &str) -> Result<(), String> {
+        let sink = mem::replace(&mut self.sink, Box::new(Silent));
+        let errors = self.errors;
+        let result = self.file(path);
+        self.sink = sink;
+        self.errors = errors;
+        result
+    }
+
+    /// Take the modes as they are now, once options and the startup
+    /// file have set them, as the ones ':reset' goes back to
+    pub fn keep_modes(&mut self) {
+        self.start = Some(self.modes());
+    }
+
+    fn modes(&self) -> Modes {
+        Modes {
+            style: self.style.clone(),
+            tolerance: self.tolerance,
+            trace: self.trace,
+            symbolic: self.symbolic,
+            strict: self.strict,
+            warn: self.warn,
+            timings: self.timings,
+            keypad: self.lexer.keypad,
+        }
+    }
+
+    /// Go back to the modes keep_modes took
+    fn restore_modes(&mut self) {
+        if let Some(modes) = self.start.clone() {
+            self.set_modes(modes);
+        }
+    }
+
+    fn set_modes(&mut self, modes: Modes) {
+        self.style = modes.style;
+        self.tolerance = modes.tolerance;
+        self.trace = modes.trace;
+        self.symbolic = modes.symbolic;
+        self.strict = modes.strict;
+        self.warn = modes.warn;
+        self.timings = modes.timings;
+        self.lexer.keypad = modes.keypad;
+    }
+
+    /// Everything the lines so far have left behind, for restore to go
+    /// back to
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            ans: self.ans.clone(),
+            history: self.history.clone(),
+            memory: self.memory.clone(),
+            variables: self.variables.clone(),
+            functions: self.functions.clone(),
+            rng: self.rng,
+            modes: self.modes(),
+        }
+    }
+
+    /// Put the session back as it was when 'snapshot' was taken
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let snapshot = snapshot.clone();
+        self.ans = snapshot.ans;
+        self.history = snapshot.history;
+        self.memory = snapshot.memory;
+        self.variables = snapshot.variables;
+        self.lexer.functions = snapshot.functions.keys().cloned().collect();
+        self.functions = snapshot.functions;
+        self.rng = snapshot.rng;
+        self.set_modes(snapshot.modes);
+        self.renamed();
+    }
+
+    /// Keep a result as the latest, for '$1' and the like
+    fn remember(&mut self, a: Value) {
+        if self.history.len() == HISTORY {
+            self.history.pop_back();
+        }
+        self.history.push_front(a.clone());
+        self.ans = a;
+        self.results += 1;
+    }
+
+    /// Evaluate lines until the input runs out
+    pub fn lines(&mut self) {
+        self.parse(Start);
+    }
+
+    /// Adding-machine mode: a line starting with an operator continues
+    /// from the previous result
+    pub fn sticky(&mut self) {
+        for op in [PLUS, MINUS, TIMES, DIVIDE] {
+            self.driver.table.insert(op, Start, token_vec![Line, Start]);
+            self.driver
+                .table
+                .insert(op, Line, token_vec![Ans, TermP, ExprP, Print, NL]);
+        }
+    }
+
+    /// names(), shared with whatever completes them, and updated as
+    /// variables and functions come and go
+    pub fn completions(&self) -> Rc<RefCell<Vec<String>>> {
+        Rc::clone(&self.completions)
+    }
+
+    /// Bring completions() up to date after names have changed
+    fn renamed(&mut self) {
+        *self.completions.borrow_mut() = self.names();
+    }
+
+    /// Names to complete at the prompt: functions, variables, words
+    /// like 'if', and ':' commands
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BUILTINS
+            .iter()
+            .map(|b| b.name)
+            .chain(KEYWORDS.iter().copied())
+            .map(String::from)
+            .chain(self.functions.keys().cloned())
+            .chain(self.variables.keys().cloned())
+            .chain(COMMANDS.iter().map(|c| format!(":{}", c)))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Check 'count' random expressions from the parse table, against
+    /// 'reference' if given, returning how many went wrong
+    pub fn difftest(&self, count: usize, reference: Option<&str>) -> Result<usize, String> {
+        difftest::run(&self.driver.table, count, reference)
+    }
+
+    /// Parse and evaluate input until 'goal' has been matched. Returns
+    /// false if the input ran out first.
+    fn parse(&mut self, goal: ENonTerminal) -> bool {
+        if self.peek() == END && goal != Start {
+            return false;
+        }
+        self.run(goal)
+    }
+
+    /// Where on the parse stack the ')' for the innermost parentheses
+    /// already opened is, skipping those for '(' yet to be read
+    fn unclosed(&self) -> Option<usize> {
+        let mut unopened = 0;
+        for (i, token) in self.driver.stack.iter().enumerate().rev() {
+            match token {
+                Terminal(OP) => unopened += 1,
+                Terminal(CP) if unopened == 0 => return Some(i),
+                Terminal(CP) => unopened -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Report the errors found on the line, now that all of it has
+    /// been read
+    fn report(&mut self) {
+        for error in self.unreported.drain(..) {
+            #[cfg(feature = "tracing")]
+            tracing::info!(line = error.line(), %error, "error");
+            if !self.quiet {
+                let input = Input {
+                    column: Some(error.column()),
+                    ..self.lexer.input()
+                };
+                self.sink.error(&input, &error.to_string());
+            }
+            self.last_error = Some(error);
+        }
+    }
+
+    /// Start the next line afresh, keeping the stacks' storage so
+    /// recovery doesn't allocate
+    fn reset(&mut self) {
+        self.values.clear();
+        self.marks.clear();
+        self.calls.clear();
+        self.operators.clear();
+        self.opened.clear();
+        self.percent = false;
+        self.conditions.clear();
+        self.skip = 0;
+        self.started = None;
+    }
+}
+
+impl Machine for Calc {
+    type T = ETerminal;
+    type N = ENonTerminal;
+    type A = EAction;
+
+    fn driver(&mut self) -> &mut Driver<ETerminal, ENonTerminal, EAction> {
+        &mut self.driver
+    }
+
+    fn peek(&mut self) -> ETerminal {
+        if self.lexeme == NONE {
+            self.lex();
+        }
+        self.lexeme
+    }
+
+    fn consume(&mut self) {
+        match self.lexeme {
+            NL => {
+                self.started = None;
+                // The end of a line parsed on past an error
+                if !self.unreported.is_empty() {
+                    self.report();
+                    self.reset();
+                }
+            }
+            OP => self.opened.push(self.position),
+            CP => {
+                self.opened.pop();
+            }
+            PLUS | MINUS | TIMES | DIVIDE | POW | APPROX if self.skip == 0 => {
+                self.operators.push((self.values.len(), self.position))
+            }
+            _ => {}
+        }
+        self.lexeme = NONE;
+    }
+
+    /// Skip to the end of the expression and report the error. That's
+    /// the end of the line or a ';', or else a ')' closing parentheses
+    /// opened before the error, after which the rest of the line is
+    /// parsed, without being evaluated, for more errors to report.
+    fn recover(&mut self, error: Error<ETerminal>, goal: ENonTerminal) -> Recovery {
+        // A line which ended too soon has already been read to its end,
+        // and a ')' which didn't fit may close parentheses to carry on from
+        let error = at_end(error, self.lexer.ended());
+        if let Error::Syntax(found @ (NL | END | CP), _) = error {
+            self.lexeme = found;
+        }
+        let at = self.failed.take().unwrap_or(self.position);
+        self.unreported
+            .push(place(error, at, &self.value, self.opened.last().copied()));
+        self.errors += 1;
+        self.lexer.end_line();
+        // Loops' stacks hold their own tokens, so skipping out of one
+        // can't pick up again inside it
+        let resume = !self.strict && self.loops.is_empty() && self.replay.is_empty();
+        self.loops.clear();
+        let mut depth = 0;
+        loop {
+            match self.lexeme {
+                NL | END => break,
+                OP => depth += 1,
+                CP if depth == 0 => break,
+                CP => depth -= 1,
+                _ => {}
+            }
+            self.lex();
+        }
+        if self.lexeme == CP && resume {
+            if let Some(i) = self.unclosed() {
+                // Carry on from the ')'
+                let stack = &mut self.driver.stack;
+                stack.truncate(i + 1);
+                let count = |action| stack.iter().filter(|t| **t == Action(action)).count();
+                let pending = count(Else).saturating_sub(count(Cond));
+                self.conditions.truncate(pending);
+                self.skip += 1;
+                return Recovery::Continue;
+            }
+        }
+        while self.lexeme != NL && self.lexeme != END {
+            self.lex();
+        }
+        self.report();
+        if self.lexeme == END || self.strict {
+            return Recovery::End;
+        }
+        self.driver.stack.clear();
+        self.reset();
+        self.lexeme = NONE;
+        if goal != Start {
+            Recovery::Stop
+        } else {
+            Recovery::Resume
+        }
+    }
+
+    fn step(&mut self) {
+        self.peak_stack = self.peak_stack.max(self.driver.stack.len());
+        self.peak_values = self.peak_values.max(self.values.len());
+        if self.trace {
+            eprint!("    {:?}:", self.lexeme);
+            for token in &self.driver.stack {
+                eprint!(" {:?}", token);
+            }
+            eprintln!();
+            eprint!("        ");
+            for value in self.values.iter().rev() {
+                eprint!(" {}", value);
+            }
+            eprintln!();
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    fn expanded(&mut self, non_terminal: ENonTerminal, lookahead: ETerminal) {
+        tracing::trace!(?non_terminal, ?lookahead, "expand");
+    }
+
+    /// Evaluate, noting where it went wrong if it did: at the operator
+    /// for arithmetic, and otherwise at the lookahead
+    fn action(&mut self, action: EAction) -> Result<(), String> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("action", ?action, values = self.values.len()).entered();
+        let mut at = self.position;
+        if self.skip == 0
+            && matches!(
+                action,
+                Negate | Add | Subtract | Times | Divide | Power | Approx
+            )
+        {
+            // An operator was read with its left operand, if it has
+            // one, on the value stack
+            let depth = self.values.len();
+            if let Some(i) = self.operators.iter().rposition(|(d, _)| d + 1 == depth) {
+                at = self.operators.remove(i).1;
+            }
+        }
+        let result = interrupted().and_then(|_| self.act(action));
+        if result.is_err() {
+            self.failed = Some(at);
+        }
+        result
+    }
+}
+
+impl Calc {
+    /// Evaluate as 'action' says
+    fn act(&mut self, action: EAction) -> Result<(), String> {
+        if self.skip > 0
+            && !matches!(
+                action,
+                Cond | Then | Else | Body | Hold | Held | Apply | Next
+            )
+        {
+            return Ok(());
+        }
+        let values = &mut self.values;
+        // Adding or subtracting a percentage, as in '200 + 15%', takes
+        // that share of the left side. Anywhere else, it's just a number.
+        let percent = mem::take(&mut self.percent);
+        match action {
+            Negate => {
+                let a = values.epop()?;
+                self.percent = percent;
+                a.negate().map(|v| values.push(v))
+            }
+            Add => {
+                let mut b = values.epop()?;
+                let a = values.epop()?;
+                if percent {
+                    b = a.clone().times(b)?;
+                }
+                a.add(b).map(|v| values.push(v))
+            }
+            Subtract => {
+                let mut b = values.epop()?;
+                let a = values.epop()?;
+                if percent {
+                    b = a.clone().times(b)?;
+                }
+                a.subtract(b).map(|v| values.push(v))
+            }
+            Percent => {
+                let a = values.epop()?;
+                let hundred = Value::Scalar(Quantity::rational(Rational::integer(100)));
+                self.percent = true;
+                a.divide(hundred).map(|v| values.push(v))
+            }
+            Times => {
+                let b = values.epop()?;
+                let a = values.epop()?;
+                a.times(b).map(|v| values.push(v))
+            }
+            Divide => {
+                let b = values.epop()?;
+                let a = values.epop()?;
+                a.divide(b).map(|v| values.push(v))
+            }
+            Push => {
+                values.push(self.value.clone());
+                Ok(())
+            }
+            Print => {
+                let a = values.epop()?;
+                let elapsed = match (self.timings, self.started.take()) {
+                    (true, Some(started)) => Some(started.elapsed().as_secs_f64() * 1e3),
+                    _ => None,
+                };
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    line = self.lexer.input().number,
+                    result = %a.styled(&self.style),
+                    "result"
+                );
+                if !self.quiet {
+                    self.sink
+                        .result(&self.lexer.input(), &a, &self.style, elapsed);
+                    match a.non_finite() {
+                        Some(x) if self.warn && x.is_nan() => self
+                            .sink
+                            .warning(&self.lexer.input(), "result is not a number"),
+                        Some(_) if self.warn => self
+                            .sink
+                            .warning(&self.lexer.input(), "result overflowed to infinity"),
+                        _ => {}
+                    }
+                }
+                self.remember(a);
+                Ok(())
+            }
+            Ans => {
+                values.push(self.ans.clone());
+                Ok(())
+            }
+            MemAdd | MemSub | MemClear => {
+                self.memory = match action {
+                    MemAdd => self.memory.clone().add(values.epop()?)?,
+                    MemSub => self.memory.clone().subtract(values.epop()?)?,
+                    _ => Value::number(0.0),
+                };
+                if !self.quiet {
+                    self.sink
+                        .memory(&self.lexer.input(), &self.memory, &self.style);
+                }
+                Ok(())
+            }
+            Recall => {
+                values.push(self.memory.clone());
+                Ok(())
+            }
+            Cond => {
+                if self.skip > 0 {
+                    self.conditions.push(None);
+                    return Ok(());
+                }
+                let holds = match values.epop()? {
+                    Value::Scalar(q) => q.val != 0.0,
+                    _ => return Err(String::from("if needs a number to test")),
+                };
+                self.conditions.push(Some(holds));
+                if !holds {
+                    self.skip += 1;
+                }
+                Ok(())
+            }
+            // Leaving the first branch, skip the second if the first was taken
+            Then => {
+                match self.conditions.last() {
+                    Some(Some(true)) => self.skip += 1,
+                    Some(Some(false)) => self.skip -= 1,
+                    _ => {}
+                }
+                Ok(())
+            }
+            Power => {
+                let b = values.epop()?;
+                let a = values.epop()?;
+                a.power(b).map(|v| values.push(v))
+            }
+            Variable => {
+                let Value::Text(name) = &self.value else {
+                    return Err(internal("expected a name"));
+                };
+                if let Some(n) = name.strip_prefix('$') {
+                    let value = n
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| self.history.get(n.checked_sub(1)?))
+                        .ok_or_else(|| format!("no result {}", name))?;
+                    values.push(value.clone());
+                    return Ok(());
+                }
+                match self.loops.iter().rev().find(|l| l.live && l.name == *name) {
+                    Some(l) => {
+                        values.push(l.val.clone());
+                        Ok(())
+                    }
+                    None if self.variables.contains_key(name) => {
+                        values.push(self.variables[name].clone());
+                        Ok(())
+                    }
+                    // Left unbound, to be simplified and echoed
+                    None if self.symbolic => {
+                        values.push(Value::Symbolic(symbolic::Poly::var(name)));
+                        Ok(())
+                    }
+                    None => Err(format!("unknown name {}", name)),
+                }
+            }
+            // A unit, unless a loop or the program has given its name a value
+            Unit => {
+                let name = unit_name(&self.value, &self.lexer);
+                let value = match self.loops.iter().rev().find(|l| l.live && l.name == name) {
+                    Some(l) => l.val.clone(),
+                    None => match (self.variables.get(name), &self.value) {
+                        (Some(value), _) | (None, value @ Value::Scalar(_)) => value.clone(),
+                        (None, _) => Value::Scalar(
+                            Quantity::unit(name).ok_or_else(|| internal("expected a unit"))?,
+                        ),
+                    },
+                };
+                values.push(value);
+                Ok(())
+            }
+            // A unit's name where a variable is named
+            Name => {
+                let name = unit_name(&self.value, &self.lexer);
+                values.push(Value::Text(String::from(name)));
+                Ok(())
+            }
+            // Start of a sum or prod body, with the name and bounds on the stack
+            Body => {
+                let mut l = Loop::new();
+                if self.skip == 0 {
+                    let hi = builtins::integer(&values.epop()?)?;
+                    let lo = builtins::integer(&values.epop()?)?;
+                    let Value::Text(name) = values.epop()? else {
+                        return Err(internal("expected a name"));
+                    };
+                    self.marks.epop()?;
+                    let product =
+                        matches!(self.calls.epop()?, Callee::Builtin(b) if b.name == "prod");
+                    let (series, first) = Series::new(product, lo, hi)?;
+                    l.name = name;
+                    l.kind = Some(Kind::Series(series));
+                    l.live = true;
+                    match first {
+                        Step::Again(v) => {
+                            l.val = v;
+                            l.recording = true;
+                        }
+                        // Parse an empty range's body without evaluating it
+                        Step::Done(v) => {
+                            l.result = Some(v);
+                            self.skip += 1;
+                        }
+                    }
+                }
+                self.loops.push(l);
+                Ok(())
+            }
+            // Start of a body to record without evaluating, as its
+            // variable comes after it
+            Hold => {
+                let mut l = Loop::new();
+                if self.skip == 0 {
+                    l.live = true;
+                    l.recording = true;
+                    self.skip += 1;
+                }
+                self.loops.push(l);
+                Ok(())
+            }
+            Held => {
+                if let Some(l) = self.loops.last_mut() {
+                    if l.live {
+                        // Drop the token after the body, read as lookahead
+                        l.tokens.pop();
+                        l.recording = false;
+                        self.skip -= 1;
+                    }
+                }
+                Ok(())
+            }
+            // With the function name, variable and point on the stack,
+            // start evaluating the held body
+            Apply => {
+                let mut l = self.loops.epop()?;
+                if !l.live {
+                    return Ok(());
+                }
+                let point = values.epop()?;
+                let Value::Text(name) = values.epop()? else {
+                    return Err(internal("expected a name"));
+                };
+                let (kind, first) = match values.epop()? {
+                    Value::Text(f) if f == "diff" => {
+                        let (diff, first) = Diff::new(&point)?;
+                        (Kind::Diff(diff), first)
+                    }
+                    Value::Text(f) if f == "solve" => {
+                        let (solve, first) = Solve::new(&point)?;
+                        (Kind::Solve(solve), first)
+                    }
+                    _ => return Err(internal("expected diff or solve")),
+                };
+                l.name = name;
+                l.kind = Some(kind);
+                match first {
+                    Step::Again(v) => {
+                        l.val = v;
+                        self.again(l);
+                    }
+                    Step::Done(v) => values.push(v),
+                }
+                Ok(())
+            }
+            // End of a body, which goes round again until the loop
+            // has its answer
+            Next => {
+                let mut l = self.loops.epop()?;
+                if !l.live {
+                    return Ok(());
+                }
+                if let Some(result) = l.result.take() {
+                    self.skip -= 1;
+                    values.push(result);
+                    return Ok(());
+                }
+                if l.recording {
+                    // Drop the token after the body, read as lookahead
+                    l.tokens.pop();
+                    l.recording = false;
+                }
+                let v = values.epop()?;
+                let Some(kind) = l.kind.as_mut() else {
+                    return Err(internal("loop not started"));
+                };
+                match kind.step(v)? {
+                    Step::Again(v) => {
+                        l.val = v;
+                        self.again(l);
+                    }
+                    Step::Done(v) => values.push(v),
+                }
+                Ok(())
+            }
+            Else => {
+                if let Some(true) = self.conditions.epop()? {
+                    self.skip -= 1;
+                }
+                Ok(())
+            }
+            Mark => {
+                self.marks.push(values.len());
+                Ok(())
+            }
+            Array => {
+                let mark = self.marks.epop()?;
+                let array = Value::array(
+                    values
+                        .get(mark..)
+                        .ok_or_else(|| internal("stack underflow"))?,
+                );
+                values.truncate(mark);
+                array.map(|v| values.push(v))
+            }
+            Begin => {
+                let Value::Text(name) = &self.value else {
+                    return Err(internal("expected a name"));
+                };
+                // Functions a program added come first, so it can
+                // replace builtins
+                let callee = match self.functions.get(name) {
+                    Some(func) => Callee::Registered(func.clone()),
+                    None => match builtins::find(name) {
+                        Some(builtin) => Callee::Builtin(builtin),
+                        None => return Err(format!("unknown function {}", name)),
+                    },
+                };
+                self.calls.push(callee);
+                self.marks.push(values.len());
+                Ok(())
+            }
+            Call => {
+                let mark = self.marks.epop()?;
+                if mark > values.len() {
+                    return Err(internal("stack underflow"));
+                }
+                let result = match self.calls.epop()? {
+                    Callee::Builtin(builtin) => {
+                        builtins::call(builtin, &values[mark..], &mut self.rng)
+                    }
+                    Callee::Registered(func) => builtins::call_registered(&func, &values[mark..]),
+                };
+                values.truncate(mark);
+                result.map(|v| values.push(v))
+            }
+            Approx => {
+                let b = values.epop()?;
+                let a = values.epop()?;
+                let (relative, absolute) = self.tolerance;
+                let close = a.approx(&b, relative, absolute)?;
+                values.push(Value::number(if close { 1.0 } else { 0.0 }));
+                Ok(())
+            }
+            Command => {
+                let mark = self.marks.epop()?;
+                let args = values.esplit_off(mark)?;
+                let Value::Text(name) = values.epop()? else {
+                    return Err(internal("expected a name"));
+                };
+                self.command(&name, &args)
+            }
+        }
+    }
+
+    /// Run a ':' command with its arguments
+    fn command(&mut self, name: &str, args: &[Value]) -> Result<(), String> {
+        match name {
+            // ':tol relative, absolute' sets how close '~=' needs
+            // things to be, and ':tol' alone shows it
+            "tol" => {
+                let mut tolerance = [self.tolerance.0, self.tolerance.1];
+                if args.len() > tolerance.len() {
+                    return Err(String::from(
+                        ":tol takes a relative and an absolute tolerance",
+                    ));
+                }
+                for (t, arg) in tolerance.iter_mut().zip(args) {
+                    *t = match arg {
+                        Value::Scalar(q) if q.dim == units::NONE && q.val >= 0.0 => q.val,
+                        _ => return Err(format!("invalid tolerance {}", arg)),
+                    };
+                }
+                self.tolerance = (tolerance[0], tolerance[1]);
+                if !self.quiet {
+                    let (relative, absolute) =
+                        (Value::number(tolerance[0]), Value::number(tolerance[1]));
+                    if self.speak_style {
+                        self.sink.note(&format!(
+                            "tolerance is relative {}, absolute {}",
+                            speak::value(&relative),
+                            speak::value(&absolute)
+                        ));
+                    } else {
+                        self.sink.note(&format!(
+                            "tolerance = relative {}, absolute {}",
+                            relative.styled(&self.style),
+                            absolute.styled(&self.style)
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            // ':precision n' prints results to n significant digits
+            "precision" => {
+                match args {
+                    [] => {}
+                    [n] => self.style.digits = precision(n)?,
+                    _ => return Err(String::from(":precision takes a number of digits")),
+                }
+                if !self.quiet {
+                    self.sink
+                        .note(&format!("precision = {}", self.style.digits));
+                }
+                Ok(())
+            }
+            // ':format fixed n', ':format sci', ':format eng' or ':format
+            // general' chooses how numbers are written
+            "format" => {
+                let notation = match args {
+                    [] => self.style.notation,
+                    [Value::Text(t)] if t == "general" => Notation::General,
+                    [Value::Text(t)] if t == "sci" => Notation::Sci,
+                    [Value::Text(t)] if t == "eng" => Notation::Eng,
+                    [Value::Text(t), places] if t == "fixed" => match builtins::integer(places) {
+                        Ok(n) if (0..=PRECISION as i128).contains(&n) => {
+                            Notation::Fixed(n as usize)
+                        }
+                        _ => return Err(format!("fixed needs 0 to {} places", PRECISION)),
+                    },
+                    _ => return Err(String::from(":format takes fixed n, sci, eng or general")),
+                };
+                self.style.notation = notation;
+                if !self.quiet {
+                    self.sink.note(&format!("format = {}", notation));
+                }
+                Ok(())
+            }
+            // ':outbase n' prints results in radix 2, 8, 10 or 16
+            "outbase" => {
+                match args {
+                    [] => {}
+                    [n] => match builtins::integer(n) {
+                        Ok(n @ (2 | 8 | 10 | 16)) => self.style.radix = n as u32,
+                        _ => return Err(String::from("outbase must be 2, 8, 10 or 16")),
+                    },
+                    _ => return Err(String::from(":outbase takes a radix")),
+                }
+                if !self.quiet {
+                    self.sink.note(&format!("outbase = {}", self.style.radix));
+                }
+                Ok(())
+            }
+            // ':separator ","' groups digits in threes, and ':separator ""'
+            // stops grouping them
+            "separator" => {
+                match args {
+                    [] => {}
+                    [Value::Text(t)] => self.style.separator = t.clone(),
+                    _ => return Err(String::from(":separator takes text, as in \",\"")),
+                }
+                if !self.quiet {
+                    self.sink
+                        .note(&format!("separator = \"{}\"", self.style.separator));
+                }
+                Ok(())
+            }
+            // ':vars' lists the previous result, the memory register and
+            // the variables and functions a program has given the calculator
+            "vars" => {
+                if !args.is_empty() {
+                    return Err(String::from(":vars takes no arguments"));
+                }
+                if self.quiet {
+                    return Ok(());
+                }
+                let mut variables: Vec<_> = self
+                    .variables
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value))
+                    .collect();
+                variables.sort_by_key(|(name, _)| *name);
+                let state = [("ans", &self.ans), ("memory", &self.memory)];
+                for (name, value) in state.into_iter().chain(variables) {
+                    if self.speak_style {
+                        self.sink
+                            .note(&format!("{} is {}", name, speak::value(value)));
+                    } else {
+                        self.sink
+                            .note(&format!("{} = {}", name, value.styled(&self.style)));
+                    }
+                }
+                let mut functions: Vec<_> = self.functions.keys().collect();
+                functions.sort();
+                for name in functions {
+                    self.sink
+                        .note(&format!("{} is a function defined by the program", name));
+                }
+                Ok(())
+            }
+            // ':clear' (or ':reset') forgets the variables, functions,
+            // previous result and memory, and puts the modes back as the
+            // options and startup file left them; ':clear x' forgets
+            // just 'x'
+            "clear" | "reset" => {
+                match args {
+                    [] => {
+                        self.variables.clear();
+                        self.functions.clear();
+                        self.lexer.functions.clear();
+                        self.ans = Value::number(0.0);
+                        self.history.clear();
+                        self.memory = Value::number(0.0);
+                        self.restore_modes();
+                    }
+                    [Value::Text(name)] => {
+                        let variable = self.variables.remove(name).is_some();
+                        let function = self.functions.remove(name).is_some();
+                        self.lexer.functions.remove(name);
+                        if !variable && !function {
+                            return Err(format!("{} isn't defined", name));
+                        }
+                    }
+                    _ => return Err(format!(":{} takes a name to forget", name)),
+                }
+                self.renamed();
+                if !self.quiet {
+                    self.sink.note("cleared");
+                }
+                Ok(())
+            }
+            // ':trace on' prints the parse and value stacks at each step,
+            // on stderr, and ':trace off' stops
+            "trace" => {
+                match args {
+                    [] => {}
+                    [Value::Text(t)] if t == "on" => self.trace = true,
+                    [Value::Text(t)] if t == "off" => self.trace = false,
+                    _ => return Err(String::from(":trace takes on or off")),
+                }
+                if !self.quiet {
+                    let state = if self.trace { "on" } else { "off" };
+                    self.sink.note(&format!("trace = {}", state));
+                }
+                Ok(())
+            }
+            // ':symbolic on' keeps unknown names as symbols, and
+            // ':symbolic off' reports them again
+            "symbolic" => {
+                match args {
+                    [] => {}
+                    [Value::Text(t)] if t == "on" => self.symbolic = true,
+                    [Value::Text(t)] if t == "off" => self.symbolic = false,
+                    _ => return Err(String::from(":symbolic takes on or off")),
+                }
+                if !self.quiet {
+                    let state = if self.symbolic { "on" } else { "off" };
+                    self.sink.note(&format!("symbolic = {}", state));
+                }
+                Ok(())
+            }
+            // ':help' lists what there is, and ':help name' says what a
+            // function does
+            "help" => {
+                let text = match args {
+                    [] => {
+                        let mut names: Vec<&str> = BUILTINS.iter().map(|b| b.name).collect();
+                        names.extend(self.functions.keys().map(|name| name.as_str()));
+                        names.sort();
+                        format!(
+                            "{}commands: :{}\nfunctions: {}\n':help name' describes one",
+                            HELP,
+                            COMMANDS.join(" :"),
+                            names.join(" ")
+                        )
+                    }
+                    [Value::Text(name)] => match builtins::find(name) {
+                        Some(builtin) => String::from(builtin.help),
+                        None if self.functions.contains_key(name) => {
+                            format!("{} is a function defined by the program", name)
+                        }
+                        None => return Err(format!("no function {}", name)),
+                    },
+                    _ => return Err(String::from(":help takes a function name")),
+                };
+                if !self.quiet {
+                    self.sink.note(&text);
+                }
+                Ok(())
+            }
+            // ':source "file"' evaluates the lines of a file, as if
+            // they'd been typed
+            "source" => match args {
+                [Value::Text(path)] => self.source(path),
+                _ => Err(String::from(
+                    ":source takes a file name, as in \"lib.calc\"",
+                )),
+            },
+            // ':stats' shows what the session has used so far
+            "stats" => {
+                if !args.is_empty() {
+                    return Err(String::from(":stats takes no arguments"));
+                }
+                if !self.quiet {
+                    self.sink
+                        .note(&format!("results {}, errors {}", self.results, self.errors));
+                    self.sink.note(&format!(
+                        "peak parse stack {}, peak value stack {}",
+                        self.peak_stack, self.peak_values
+                    ));
+                }
+                Ok(())
+            }
+            _ => Err(format!("unknown command :{}", name)),
+        }
+    }
+}
+
+/// Fetch a number of significant digits to print
+fn precision(arg: &Value) -> Result<usize, String> {
+    match builtins::integer(arg) {
+        Ok(n) if (1..=PRECISION as i128).contains(&n) => Ok(n as usize),
+        _ => Err(format!("precision must be from 1 to {}", PRECISION)),
+    }
+}
+
+/// Evaluate 'input' without printing anything, returning the last
+/// result, or else the last error or panic
+fn evaluate(input: &str) -> Result<Value, String> {
+    let mut calc = Calc::new(Box::new(Cursor::new(input.as_bytes().to_vec())));
+    calc.quiet = true;
+    if let Err(payload) = panic::catch_unwind(panic::AssertUnwindSafe(|| calc.parse(Start))) {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_default(),
+        };
+        return Err(format!("panic: {}", message));
+    }
+    match calc.last_error {
+        Some(error) => Err(error.to_string()),
+        None => Ok(calc.ans),
+    }
+}
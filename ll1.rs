@@ -0,0 +1,579 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+//! A table-driven LL(1) parser whose actions run as soon as they
+//! come off the stack. The calculator is one language built on it;
+//! others supply their own terminals, non-terminals and actions.
+//!
+//! A language lists its terminals and non-terminals as Symbols,
+//! builds a Table from its productions with 'build', and implements
+//! Machine to read tokens, run actions and recover from errors.
+//! Machine::run then does the parsing.
+//!
+//! ```
+//! use calc::ll1::{build, Driver, Error, Machine, Recovery, Symbol, Table, Token::*};
+//!
+//! // Sums of ones, as in 1+1+1, counting them as they're read:
+//! // Sum -> one count More, More -> + one count More | empty
+//! #[derive(PartialEq, Hash, Eq, Clone, Copy, Debug)]
+//! enum T {
+//!     One,
+//!     Plus,
+//!     End,
+//! }
+//!
+//! #[derive(PartialEq, Hash, Eq, Clone, Copy, Debug)]
+//! enum N {
+//!     Sum,
+//!     More,
+//! }
+//!
+//! impl Symbol for T {
+//!     const ALL: &'static [T] = &[T::One, T::Plus, T::End];
+//!
+//!     fn index(self) -> usize {
+//!         self as usize
+//!     }
+//! }
+//!
+//! impl Symbol for N {
+//!     const ALL: &'static [N] = &[N::Sum, N::More];
+//!
+//!     fn index(self) -> usize {
+//!         self as usize
+//!     }
+//! }
+//!
+//! struct Counter {
+//!     driver: Driver<T, N, ()>,
+//!     // Read from the end
+//!     input: Vec<T>,
+//!     count: usize,
+//! }
+//!
+//! impl Machine for Counter {
+//!     type T = T;
+//!     type N = N;
+//!     type A = ();
+//!
+//!     fn driver(&mut self) -> &mut Driver<T, N, ()> {
+//!         &mut self.driver
+//!     }
+//!
+//!     fn peek(&mut self) -> T {
+//!         self.input.last().copied().unwrap_or(T::End)
+//!     }
+//!
+//!     fn consume(&mut self) {
+//!         self.input.pop();
+//!     }
+//!
+//!     fn action(&mut self, _action: ()) -> Result<(), String> {
+//!         self.count += 1;
+//!         Ok(())
+//!     }
+//!
+//!     fn recover(&mut self, _error: Error<T>, _goal: N) -> Recovery {
+//!         Recovery::End
+//!     }
+//! }
+//!
+//! let productions = vec![
+//!     (N::Sum, vec![Terminal(T::One), Action(()), NonTerminal(N::More)], false),
+//!     (
+//!         N::More,
+//!         vec![Terminal(T::Plus), Terminal(T::One), Action(()), NonTerminal(N::More)],
+//!         false,
+//!     ),
+//!     (N::More, vec![], false),
+//! ];
+//! let table = Table::from_entries(build(&productions, T::End).unwrap());
+//! let mut counter = Counter {
+//!     driver: Driver::new(table),
+//!     input: vec![T::One, T::Plus, T::One, T::Plus, T::One],
+//!     count: 0,
+//! };
+//! assert!(counter.run(N::Sum));
+//! assert_eq!(counter.count, 3);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// Something on the right hand side of a production: a terminal to
+/// match, a non-terminal to expand, or an action to run
+#[derive(PartialEq, Hash, Eq, Clone, Copy, Debug)]
+pub enum Token<T, N, A> {
+    /// To be matched by the lookahead
+    Terminal(T),
+    /// To be expanded by the table
+    NonTerminal(N),
+    /// To be run by the Machine
+    Action(A),
+}
+use Token::*;
+
+/// Terminals and non-terminals, which index the parse table
+pub trait Symbol: Copy + Eq + Debug + 'static {
+    /// Each one, in the order 'index' counts them
+    const ALL: &'static [Self];
+
+    /// Where it comes in ALL
+    fn index(self) -> usize;
+}
+
+/// Productions by lookahead and non-terminal, as 'build' finds them
+pub type Entries<T, N, A> = HashMap<(T, N), Vec<Token<T, N, A>>>;
+
+/// Entries for a table built in, in a row of terminals for each
+/// non-terminal
+pub type Fixed<T, N, A> = &'static [Option<&'static [Token<T, N, A>]>];
+
+/// The production to expand each non-terminal by, for each lookahead.
+/// The built-in table is static; changing one makes a copy.
+pub struct Table<T: 'static, N: 'static, A: 'static> {
+    fixed: Fixed<T, N, A>,
+    // Empty until something changes
+    changed: Vec<Option<Vec<Token<T, N, A>>>>,
+}
+
+impl<T: Symbol, N: Symbol, A: Copy + 'static> Table<T, N, A> {
+    /// A table built in, as build.rs writes them out
+    pub const fn new(fixed: Fixed<T, N, A>) -> Table<T, N, A> {
+        Table {
+            fixed,
+            changed: Vec::new(),
+        }
+    }
+
+    /// A table from what 'build' found
+    pub fn from_entries(entries: Entries<T, N, A>) -> Table<T, N, A> {
+        let mut table = Table::new(&[]);
+        for ((t, n), tokens) in entries {
+            table.insert(t, n, tokens);
+        }
+        table
+    }
+
+    fn slot(t: T, n: N) -> usize {
+        n.index() * T::ALL.len() + t.index()
+    }
+
+    /// What 'n' expands to with 't' as the lookahead, if anything
+    pub fn get(&self, t: T, n: N) -> Option<&[Token<T, N, A>]> {
+        let slot = Self::slot(t, n);
+        if self.changed.is_empty() {
+            self.fixed.get(slot).copied().flatten()
+        } else {
+            self.changed[slot].as_deref()
+        }
+    }
+
+    /// Expand 'n' to 'tokens' with 't' as the lookahead
+    pub fn insert(&mut self, t: T, n: N, tokens: Vec<Token<T, N, A>>) {
+        if self.changed.is_empty() {
+            self.changed = self.fixed.iter().map(|e| e.map(<[_]>::to_vec)).collect();
+            self.changed.resize(T::ALL.len() * N::ALL.len(), None);
+        }
+        self.changed[Self::slot(t, n)] = Some(tokens);
+    }
+
+    /// Each lookahead and non-terminal with a production
+    pub fn entries(&self) -> impl Iterator<Item = (T, N, &[Token<T, N, A>])> {
+        N::ALL.iter().flat_map(move |n| {
+            T::ALL
+                .iter()
+                .filter_map(move |t| self.get(*t, *n).map(|tokens| (*t, *n, tokens)))
+        })
+    }
+}
+
+/// Parse table and stack
+pub struct Driver<T: 'static, N: 'static, A: 'static> {
+    pub table: Table<T, N, A>,
+    /// What's still to be matched, expanded or run, next on top
+    pub stack: Vec<Token<T, N, A>>,
+}
+
+impl<T: Symbol, N: Symbol, A: Copy + 'static> Driver<T, N, A> {
+    /// A driver for 'table', with nothing on the stack
+    pub fn new(table: Table<T, N, A>) -> Driver<T, N, A> {
+        Driver {
+            table,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Whether 't' could come next with 'n' on top of the stack. An
+    /// empty production for 'n' only lets 't' through when what's
+    /// under it on the stack can start with 't'.
+    pub fn accepts(&self, t: T, n: N) -> bool {
+        let mut stack = self.stack.clone();
+        stack.push(NonTerminal(n));
+        while let Some(token) = stack.pop() {
+            match token {
+                Terminal(terminal) => return terminal == t,
+                NonTerminal(n) => match self.table.get(t, n) {
+                    Some(tokens) => stack.extend(tokens.iter().rev()),
+                    None => return false,
+                },
+                Action(_) => {}
+            }
+        }
+        true
+    }
+}
+
+/// Where to go once an error has been dealt with
+pub enum Recovery {
+    /// Start over on the same goal
+    Resume,
+    /// Give up on this goal, but there's more input
+    Stop,
+    /// Carry on with the stack as it was left
+    Continue,
+    /// The input has run out
+    End,
+}
+
+/// Why 'run' stopped to recover
+pub enum Error<T> {
+    /// The lookahead didn't fit; these terminals would have
+    Syntax(T, Vec<T>),
+    /// An action failed
+    Action(String),
+}
+
+/// A language parsed by an LL(1) table whose actions run as soon as
+/// they come off the stack. The language supplies the tokens, the
+/// actions and what to do about errors; 'run' does the parsing.
+pub trait Machine {
+    type T: Symbol;
+    type N: Symbol;
+    type A: Copy + Debug + 'static;
+
+    /// The table and stack to parse with
+    fn driver(&mut self) -> &mut Driver<Self::T, Self::N, Self::A>;
+
+    /// The lookahead, reading one if there isn't one yet
+    fn peek(&mut self) -> Self::T;
+
+    /// Done with the lookahead
+    fn consume(&mut self);
+
+    /// Run 'action', failing with a message to recover from
+    fn action(&mut self, action: Self::A) -> Result<(), String>;
+
+    /// Report 'error' and skip to somewhere parsing can pick up
+    /// again. A token which didn't fit has already been consumed.
+    fn recover(&mut self, error: Error<Self::T>, goal: Self::N) -> Recovery;
+
+    /// Called before each step, for tracing
+    fn step(&mut self) {}
+
+    /// Called after 'non_terminal' has been expanded by the production
+    /// for 'lookahead', for tracing
+    fn expanded(&mut self, _non_terminal: Self::N, _lookahead: Self::T) {}
+
+    /// Parse and run input until 'goal' has been matched. Returns
+    /// false if the input ran out first.
+    fn run(&mut self, goal: Self::N) -> bool {
+        self.driver().stack.clear();
+        self.driver().stack.push(NonTerminal(goal));
+        loop {
+            self.step();
+            let error = match self.driver().stack.pop() {
+                Some(Terminal(terminal)) => {
+                    let lookahead = self.peek();
+                    self.consume();
+                    if lookahead == terminal {
+                        continue;
+                    }
+                    Error::Syntax(lookahead, vec![terminal])
+                }
+                Some(NonTerminal(non_terminal)) => {
+                    let lookahead = self.peek();
+                    let driver = self.driver();
+                    if let Some(tokens) = driver.table.get(lookahead, non_terminal) {
+                        driver.stack.extend(tokens.iter().rev());
+                        self.expanded(non_terminal, lookahead);
+                        continue;
+                    }
+                    let expected = driver
+                        .table
+                        .entries()
+                        .filter(|(t, n, _)| *n == non_terminal && driver.accepts(*t, *n))
+                        .map(|(t, _, _)| t)
+                        .collect();
+                    self.consume();
+                    Error::Syntax(lookahead, expected)
+                }
+                Some(Action(action)) => match self.action(action) {
+                    Ok(()) => continue,
+                    Err(message) => Error::Action(message),
+                },
+                None => return true,
+            };
+            match self.recover(error, goal) {
+                Recovery::Resume => self.driver().stack.push(NonTerminal(goal)),
+                Recovery::Stop => return true,
+                Recovery::Continue => {}
+                Recovery::End => return false,
+            }
+        }
+    }
+}
+
+/// A non-terminal and what it expands to. Marked preferred, it's taken
+/// over any other it conflicts with.
+pub type Production<T, N, A> = (N, Vec<Token<T, N, A>>, bool);
+
+/// Build the table for a grammar given as productions, the first of
+/// which is for the start symbol, with 'end' following the whole
+/// input. Fails unless the grammar is LL(1), each lookahead picking
+/// out one production, except where a preferred one settles it.
+pub fn build<T, N, A>(
+    productions: &[Production<T, N, A>],
+    end: T,
+) -> Result<Entries<T, N, A>, String>
+where
+    T: Copy + Eq + Hash + Debug,
+    N: Copy + Eq + Hash + Debug,
+    A: Copy + Eq + Debug,
+{
+    let Some((start, _, _)) = productions.first() else {
+        return Err(String::from("no productions"));
+    };
+    for (_, tokens, _) in productions {
+        for token in tokens {
+            if let NonTerminal(n) = token {
+                if !productions.iter().any(|(lhs, _, _)| lhs == n) {
+                    return Err(format!("no production for {:?}", n));
+                }
+            }
+        }
+    }
+
+    // What a sequence can start with, and whether it can be empty
+    let first = |first: &HashMap<N, Vec<T>>, nullable: &Vec<N>, tokens: &[Token<T, N, A>]| {
+        let mut set: Vec<T> = Vec::new();
+        for token in tokens {
+            match token {
+                Terminal(t) => {
+                    set.push(*t);
+                    return (set, false);
+                }
+                NonTerminal(n) => {
+                    set.extend(first.get(n).into_iter().flatten());
+                    if !nullable.contains(n) {
+                        return (set, false);
+                    }
+                }
+                Action(_) => {}
+            }
+        }
+        (set, true)
+    };
+
+    // Both sets grow until nothing changes
+    let mut firsts: HashMap<N, Vec<T>> = HashMap::new();
+    let mut nullable: Vec<N> = Vec::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (lhs, tokens, _) in productions {
+            let (set, empty) = first(&firsts, &nullable, tokens);
+            let known = firsts.entry(*lhs).or_default();
+            for t in set {
+                if !known.contains(&t) {
+                    known.push(t);
+                    changed = true;
+                }
+            }
+            if empty && !nullable.contains(lhs) {
+                nullable.push(*lhs);
+                changed = true;
+            }
+        }
+    }
+    let mut follows: HashMap<N, Vec<T>> = HashMap::from([(*start, vec![end])]);
+    changed = true;
+    while changed {
+        changed = false;
+        for (lhs, tokens, _) in productions {
+            for (i, token) in tokens.iter().enumerate() {
+                let NonTerminal(n) = token else { continue };
+                let (mut set, empty) = first(&firsts, &nullable, &tokens[i + 1..]);
+                if empty {
+                    set.extend(follows.get(lhs).into_iter().flatten());
+                }
+                let known = follows.entry(*n).or_default();
+                for t in set {
+                    if !known.contains(&t) {
+                        known.push(t);
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut table: Entries<T, N, A> = HashMap::new();
+    let mut preferred: HashMap<(T, N), bool> = HashMap::new();
+    for (lhs, tokens, prefer) in productions {
+        let (mut set, empty) = first(&firsts, &nullable, tokens);
+        if empty {
+            set.extend(follows.get(lhs).into_iter().flatten());
+        }
+        for t in set {
+            match (table.get(&(t, *lhs)), preferred.get(&(t, *lhs))) {
+                (Some(other), Some(other_prefer)) if other != tokens => {
+                    if *other_prefer == *prefer {
+                        return Err(format!(
+                            "not LL(1): {:?} could start {:?} as {:?} or {:?}",
+                            t, lhs, other, tokens
+                        ));
+                    }
+                    if *other_prefer {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            table.insert((t, *lhs), tokens.clone());
+            preferred.insert((t, *lhs), *prefer);
+        }
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tiny expression grammar: E -> T E', E' -> + T E' | empty,
+    // T -> n | ( E )
+    #[derive(PartialEq, Hash, Eq, Clone, Copy, Debug)]
+    enum T {
+        N,
+        Plus,
+        Open,
+        Close,
+        End,
+    }
+
+    #[derive(PartialEq, Hash, Eq, Clone, Copy, Debug)]
+    enum N {
+        E,
+        EP,
+        Term,
+    }
+
+    impl Symbol for T {
+        const ALL: &'static [T] = &[T::N, T::Plus, T::Open, T::Close, T::End];
+
+        fn index(self) -> usize {
+            self as usize
+        }
+    }
+
+    impl Symbol for N {
+        const ALL: &'static [N] = &[N::E, N::EP, N::Term];
+
+        fn index(self) -> usize {
+            self as usize
+        }
+    }
+
+    fn grammar() -> Vec<Production<T, N, ()>> {
+        vec![
+            (N::E, vec![NonTerminal(N::Term), NonTerminal(N::EP)], false),
+            (
+                N::EP,
+                vec![Terminal(T::Plus), NonTerminal(N::Term), NonTerminal(N::EP)],
+                false,
+            ),
+            (N::EP, vec![], false),
+            (N::Term, vec![Terminal(T::N)], false),
+            (
+                N::Term,
+                vec![Terminal(T::Open), NonTerminal(N::E), Terminal(T::Close)],
+                false,
+            ),
+        ]
+    }
+
+    #[test]
+    fn builds_first_and_follow() {
+        let table = build(&grammar(), T::End).unwrap();
+        assert_eq!(
+            table[&(T::Open, N::E)],
+            vec![NonTerminal(N::Term), NonTerminal(N::EP)]
+        );
+        assert_eq!(table[&(T::Plus, N::EP)][0], Terminal(T::Plus));
+        // The empty production goes where E' can be followed
+        assert_eq!(table[&(T::End, N::EP)], vec![]);
+        assert_eq!(table[&(T::Close, N::EP)], vec![]);
+        assert!(!table.contains_key(&(T::Plus, N::Term)));
+    }
+
+    #[test]
+    fn rejects_conflicts() {
+        let mut productions = grammar();
+        productions.push((N::Term, vec![Terminal(T::N), Terminal(T::Plus)], false));
+        assert!(build(&productions, T::End)
+            .unwrap_err()
+            .starts_with("not LL(1)"));
+    }
+
+    #[test]
+    fn preferred_settles_conflicts() {
+        let mut productions = grammar();
+        productions.push((N::Term, vec![Terminal(T::N), Terminal(T::Plus)], true));
+        let table = build(&productions, T::End).unwrap();
+        assert_eq!(
+            table[&(T::N, N::Term)],
+            vec![Terminal(T::N), Terminal(T::Plus)]
+        );
+    }
+
+    #[test]
+    fn accepts_what_the_stack_allows() {
+        let table = Table::from_entries(build(&grammar(), T::End).unwrap());
+        let mut driver = Driver::new(table);
+        driver.stack.push(Terminal(T::Close));
+        assert!(driver.accepts(T::Plus, N::EP));
+        assert!(driver.accepts(T::Close, N::EP));
+        // E' could be empty before the end, but not with ')' to come
+        assert!(!driver.accepts(T::End, N::EP));
+        driver.stack.clear();
+        assert!(driver.accepts(T::End, N::EP));
+        assert!(!driver.accepts(T::Close, N::Term));
+    }
+
+    #[test]
+    fn rejects_missing_productions() {
+        let productions: Vec<Production<T, N, ()>> =
+            vec![(N::E, vec![NonTerminal(N::Term)], false)];
+        assert_eq!(
+            build(&productions, T::End).unwrap_err(),
+            "no production for Term"
+        );
+    }
+}
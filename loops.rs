@@ -0,0 +1,293 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::builtins::int_value;
+use crate::units::{self, Dim, Quantity};
+use crate::value::Value;
+
+/// What a loop wants after each evaluation of its body
+pub enum Step {
+    // Evaluate the body again with the variable set to this
+    Again(Value),
+    // Finished, with this result
+    Done(Value),
+}
+
+/// The different loops, each working through the values of its body
+pub enum Kind {
+    Series(Series),
+    Diff(Diff),
+    Solve(Solve),
+}
+
+impl Kind {
+    pub fn step(&mut self, v: Value) -> Result<Step, String> {
+        match self {
+            Kind::Series(s) => s.step(v),
+            Kind::Diff(d) => d.step(v),
+            Kind::Solve(s) => s.step(v),
+        }
+    }
+}
+
+/// Refuse a sum or prod of more terms than this, which would take too
+/// long to evaluate
+const MAX_TERMS: i128 = 1_000_000;
+
+/// sum or prod over whole numbers from lo to hi
+pub struct Series {
+    product: bool,
+    i: i128,
+    hi: i128,
+    acc: Option<Value>,
+}
+
+impl Series {
+    /// An empty range is finished before it starts
+    pub fn new(product: bool, lo: i128, hi: i128) -> Result<(Series, Step), String> {
+        if hi.checked_sub(lo).is_none_or(|n| n >= MAX_TERMS) {
+            let name = if product { "prod" } else { "sum" };
+            return Err(format!("{} of more than {} terms", name, MAX_TERMS));
+        }
+        let first = if lo <= hi {
+            Step::Again(int_value(lo))
+        } else {
+            Step::Done(int_value(if product { 1 } else { 0 }))
+        };
+        let series = Series {
+            product,
+            i: lo,
+            hi,
+            acc: None,
+        };
+        Ok((series, first))
+    }
+
+    fn step(&mut self, v: Value) -> Result<Step, String> {
+        let acc = match self.acc.take() {
+            None => v,
+            Some(acc) if self.product => acc.times(v)?,
+            Some(acc) => acc.add(v)?,
+        };
+        if self.i == self.hi {
+            return Ok(Step::Done(acc));
+        }
+        self.acc = Some(acc);
+        self.i += 1;
+        Ok(Step::Again(int_value(self.i)))
+    }
+}
+
+/// Ridders' method: central differences with the step shrinking by CON
+/// each time, extrapolated towards zero step until the error estimate
+/// stops improving
+const CON: f64 = 1.4;
+const CON2: f64 = CON * CON;
+const MAX_STEPS: usize = 10;
+const SAFE: f64 = 2.0;
+
+pub struct Diff {
+    x0: Quantity,
+    h: f64,
+
+    // f(x0 + h), waiting for f(x0 - h)
+    plus: Option<f64>,
+
+    // Units of f
+    dim: Option<Dim>,
+
+    // Extrapolations from the previous step size
+    row: Vec<f64>,
+
+    best: f64,
+    err: f64,
+}
+
+impl Diff {
+    pub fn new(x0: &Value) -> Result<(Diff, Step), String> {
+        let Value::Scalar(x0) = *x0 else {
+            return Err(String::from("diff needs a number to differentiate at"));
+        };
+        let diff = Diff {
+            x0,
+            h: 0.1 * x0.val.abs().max(1.0),
+            plus: None,
+            dim: None,
+            row: Vec::new(),
+            best: f64::NAN,
+            err: f64::INFINITY,
+        };
+        let first = Step::Again(diff.at(diff.h));
+        Ok((diff, first))
+    }
+
+    fn at(&self, offset: f64) -> Value {
+        Value::Scalar(Quantity {
+            val: self.x0.val + offset,
+            dim: self.x0.dim,
+            exact: None,
+        })
+    }
+
+    fn step(&mut self, v: Value) -> Result<Step, String> {
+        let Value::Scalar(f) = v else {
+            return Err(String::from("diff needs an expression giving a number"));
+        };
+        if *self.dim.get_or_insert(f.dim) != f.dim {
+            return Err(String::from("diff expression changes units"));
+        }
+        let Some(plus) = self.plus.take() else {
+            self.plus = Some(f.val);
+            return Ok(Step::Again(self.at(-self.h)));
+        };
+        let mut row = vec![(plus - f.val) / (2.0 * self.h)];
+        if self.row.is_empty() {
+            self.best = row[0];
+        }
+        let mut fac = CON2;
+        for (j, prev) in self.row.iter().enumerate() {
+            let next = (row[j] * fac - prev) / (fac - 1.0);
+            fac *= CON2;
+            let err = (next - row[j]).abs().max((next - prev).abs());
+            if err <= self.err {
+                self.err = err;
+                self.best = next;
+            }
+            row.push(next);
+        }
+        // Stop when the highest order gets worse, as rounding takes over
+        let worse = match (row.last(), self.row.last()) {
+            (Some(a), Some(b)) => (a - b).abs() >= SAFE * self.err,
+            _ => false,
+        };
+        self.row = row;
+        if worse || self.row.len() >= MAX_STEPS {
+            let mut dim = self.dim.unwrap_or(self.x0.dim);
+            for (d, x) in dim.iter_mut().zip(self.x0.dim) {
+                *d = d.checked_sub(x).ok_or_else(units::range)?;
+            }
+            return Ok(Step::Done(Value::Scalar(Quantity {
+                val: self.best,
+                dim,
+                exact: None,
+            })));
+        }
+        self.h /= CON;
+        Ok(Step::Again(self.at(self.h)))
+    }
+}
+
+/// Give up on finding a root after this many evaluations
+const MAX_TRIES: usize = 200;
+
+/// Root finding by the secant method, falling back to bisection once
+/// the root is bracketed and the secant step would leave the bracket
+pub struct Solve {
+    guess: Quantity,
+    x: f64,
+
+    // Previous point and its value
+    prev: Option<(f64, f64)>,
+
+    // Points either side of the root, with their values
+    bracket: Option<((f64, f64), (f64, f64))>,
+
+    // Units of f
+    dim: Option<Dim>,
+
+    tries: usize,
+}
+
+impl Solve {
+    pub fn new(guess: &Value) -> Result<(Solve, Step), String> {
+        let Value::Scalar(guess) = *guess else {
+            return Err(String::from("solve needs a number to start from"));
+        };
+        let solve = Solve {
+            guess,
+            x: guess.val,
+            prev: None,
+            bracket: None,
+            dim: None,
+            tries: 0,
+        };
+        let first = Step::Again(solve.at(solve.x));
+        Ok((solve, first))
+    }
+
+    fn at(&self, x: f64) -> Value {
+        Value::Scalar(Quantity {
+            val: x,
+            dim: self.guess.dim,
+            exact: None,
+        })
+    }
+
+    fn step(&mut self, v: Value) -> Result<Step, String> {
+        let Value::Scalar(f) = v else {
+            return Err(String::from("solve needs an expression giving a number"));
+        };
+        if *self.dim.get_or_insert(f.dim) != f.dim {
+            return Err(String::from("solve expression changes units"));
+        }
+        let (x, fx) = (self.x, f.val);
+        if fx == 0.0 {
+            return Ok(Step::Done(self.at(x)));
+        }
+        self.tries += 1;
+        // Back off from a pole or other trouble spot
+        if !fx.is_finite() {
+            let Some((xp, _)) = self.prev else {
+                return Err(String::from("solve did not converge"));
+            };
+            self.x = (x + xp) / 2.0;
+            return Ok(Step::Again(self.at(self.x)));
+        }
+        if self.tries > MAX_TRIES {
+            return Err(String::from("solve did not converge"));
+        }
+        // Keep the bracket as tight as possible
+        self.bracket = match (self.bracket, self.prev) {
+            (Some((a, b)), _) if (fx < 0.0) == (a.1 < 0.0) => Some(((x, fx), b)),
+            (Some((a, _)), _) => Some((a, (x, fx))),
+            (None, Some(p)) if (fx < 0.0) != (p.1 < 0.0) => Some((p, (x, fx))),
+            (None, _) => None,
+        };
+        let next = match self.prev {
+            None => x + 1e-3 * x.abs().max(1.0),
+            Some((xp, fp)) => x - fx * (x - xp) / (fx - fp),
+        };
+        let next = match self.bracket {
+            Some(((a, _), (b, _))) if !(next > a.min(b) && next < a.max(b)) => (a + b) / 2.0,
+            _ if !next.is_finite() => return Err(String::from("solve did not converge")),
+            _ => next,
+        };
+        let close = f64::EPSILON * x.abs().max(f64::MIN_POSITIVE);
+        if (next - x).abs() <= close {
+            return Ok(Step::Done(self.at(next)));
+        }
+        if let Some(((a, _), (b, _))) = self.bracket {
+            if (a - b).abs() <= close {
+                return Ok(Step::Done(self.at(next)));
+            }
+        }
+        self.prev = Some((x, fx));
+        self.x = next;
+        Ok(Step::Again(self.at(next)))
+    }
+}
@@ -0,0 +1,172 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use calc::sink::{Bare, Csv, Json, Stdout};
+use calc::{drill, reduce, tutorial, Calc, CAPABILITIES};
+use std::io::{Cursor, IsTerminal};
+use std::path::Path;
+use std::process::ExitCode;
+
+mod editor;
+mod options;
+
+/// The startup file: $CALC_RC, or else ~/.calcrc if there is one
+fn rc_path() -> Option<String> {
+    if let Ok(path) = std::env::var("CALC_RC") {
+        return Some(path);
+    }
+    let path = format!("{}/.calcrc", std::env::var("HOME").ok()?);
+    Path::new(&path).is_file().then_some(path)
+}
+
+fn main() -> ExitCode {
+    let options = match options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("Try 'calc --help' for the options.");
+            return ExitCode::FAILURE;
+        }
+    };
+    if options.help {
+        print!("{}", options::usage());
+        return ExitCode::SUCCESS;
+    }
+    if options.version {
+        println!("calc {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+    if options.capabilities {
+        for (name, present) in CAPABILITIES {
+            println!("{} {}", name, if *present { "yes" } else { "no" });
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    // Prompts and labelled results are for a person at a terminal;
+    // pipes get just the values
+    let interactive =
+        options.interactive || (std::io::stdin().is_terminal() && std::io::stdout().is_terminal());
+
+    let mut calc = Calc::new(Box::new(std::io::stdin()));
+    calc.lexer.keypad = options.keypad;
+    calc.strict = options.strict;
+    calc.warn = !options.no_warn;
+    if let Some(path) = &options.grammar {
+        if let Err(message) = calc.grammar(path) {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    // Definitions and modes for every session, which the options
+    // below can then override
+    if !options.no_rc {
+        if let Some(path) = rc_path() {
+            if let Err(message) = calc.startup(&path) {
+                eprintln!("{}", message);
+            }
+        }
+    }
+
+    calc.timings = options.timings;
+    calc.trace = options.trace;
+    calc.symbolic = options.symbolic;
+    calc.speak_style = options.speak_style;
+    calc.sink = if options.csv {
+        Box::new(Csv::new())
+    } else if options.json {
+        Box::new(Json)
+    } else if options.quiet || !(interactive || options.speak_style || options.banner) {
+        Box::new(Bare)
+    } else {
+        Box::new(Stdout {
+            speak_style: options.speak_style,
+            banner: options.banner,
+        })
+    };
+    if let Some(precision) = options.precision {
+        calc.style.digits = precision;
+    }
+    if let Some(separator) = &options.separator {
+        calc.style.separator = separator.clone();
+    }
+    if let Some(radix) = options.radix {
+        calc.style.radix = radix;
+    }
+
+    if options.sticky {
+        calc.sticky();
+    }
+    calc.keep_modes();
+
+    if let Some(count) = options.difftest {
+        match calc.difftest(count, options.reference.as_deref()) {
+            Ok(0) => {}
+            Ok(_) => return ExitCode::FAILURE,
+            Err(message) => {
+                eprintln!("{}", message);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if let Some(path) = &options.reduce {
+        if let Err(message) = reduce::run(path) {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    } else if options.tutorial {
+        tutorial::run(&mut calc);
+    } else if options.drill {
+        drill::run(&mut calc, options.level.unwrap_or(1));
+    } else {
+        for path in &options.files {
+            if let Err(message) = calc.file(path) {
+                eprintln!("{}", message);
+                return ExitCode::FAILURE;
+            }
+            if calc.strict && calc.errors > 0 {
+                return ExitCode::FAILURE;
+            }
+        }
+        if !options.expressions.is_empty() {
+            // Each on its own, so one ending in an operator doesn't
+            // continue into the next
+            for expression in &options.expressions {
+                let text = format!("{}\n", expression);
+                calc.read(None, Box::new(Cursor::new(text.into_bytes())));
+                calc.lines();
+                if calc.strict && calc.errors > 0 {
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else if options.files.is_empty() {
+            // People typing get a prompt, line editing and history
+            if interactive {
+                editor::catch_interrupts();
+                calc.read(None, Box::new(editor::Editor::new(calc.completions())));
+            } else {
+                calc.read(None, Box::new(std::io::stdin()));
+            }
+            calc.lines();
+        }
+        if calc.errors > 0 {
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,332 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use calc::format::PRECISION;
+use std::path::Path;
+
+/// A command line option, with what its argument is called if it
+/// takes one
+struct Opt {
+    names: &'static [&'static str],
+    arg: Option<&'static str>,
+    help: &'static str,
+}
+
+const OPTIONS: &[Opt] = &[
+    Opt {
+        names: &["-e"],
+        arg: Some("EXPR"),
+        help: "evaluate EXPR; may be given more than once",
+    },
+    Opt {
+        names: &["-f", "--file"],
+        arg: Some("FILE"),
+        help: "evaluate the lines of FILE; may be given more than once",
+    },
+    Opt {
+        names: &["-i", "--interactive"],
+        arg: None,
+        help: "prompt and label results even when not at a terminal",
+    },
+    Opt {
+        names: &["-q", "--quiet"],
+        arg: None,
+        help: "print bare values, with errors on stderr, as in pipes",
+    },
+    Opt {
+        names: &["--json"],
+        arg: None,
+        help: "print a JSON object for each line",
+    },
+    Opt {
+        names: &["--csv"],
+        arg: None,
+        help: "print a CSV row for each line",
+    },
+    Opt {
+        names: &["--speak-style"],
+        arg: None,
+        help: "print results in words for screen readers",
+    },
+    Opt {
+        names: &["--banner"],
+        arg: None,
+        help: "print results in large characters",
+    },
+    Opt {
+        names: &["--precision"],
+        arg: Some("N"),
+        help: "print N significant digits",
+    },
+    Opt {
+        names: &["--separator"],
+        arg: Some("TEXT"),
+        help: "put TEXT between groups of three digits",
+    },
+    Opt {
+        names: &["--hex"],
+        arg: None,
+        help: "print results in hexadecimal",
+    },
+    Opt {
+        names: &["--oct"],
+        arg: None,
+        help: "print results in octal",
+    },
+    Opt {
+        names: &["--bin"],
+        arg: None,
+        help: "print results in binary",
+    },
+    Opt {
+        names: &["--timings"],
+        arg: None,
+        help: "show how long each line took",
+    },
+    Opt {
+        names: &["--trace"],
+        arg: None,
+        help: "show the parse and value stacks at each step",
+    },
+    Opt {
+        names: &["--symbolic"],
+        arg: None,
+        help: "keep unknown names as symbols, as in 2*x + 3*x",
+    },
+    Opt {
+        names: &["--strict"],
+        arg: None,
+        help: "stop at the first error",
+    },
+    Opt {
+        names: &["--no-warn"],
+        arg: None,
+        help: "don't point out results which are infinite or not a number",
+    },
+    Opt {
+        names: &["--keypad"],
+        arg: None,
+        help: "accept 'x' to multiply and ':' to divide",
+    },
+    Opt {
+        names: &["--sticky"],
+        arg: None,
+        help: "lines starting with an operator continue from the last result",
+    },
+    Opt {
+        names: &["--grammar"],
+        arg: Some("FILE"),
+        help: "parse with the grammar in FILE instead of the built-in one",
+    },
+    Opt {
+        names: &["--no-rc"],
+        arg: None,
+        help: "don't load $CALC_RC or ~/.calcrc",
+    },
+    Opt {
+        names: &["tutorial"],
+        arg: None,
+        help: "walk through the features",
+    },
+    Opt {
+        names: &["drill"],
+        arg: None,
+        help: "practice arithmetic",
+    },
+    Opt {
+        names: &["--level"],
+        arg: Some("N"),
+        help: "make drill problems of difficulty N",
+    },
+    Opt {
+        names: &["--reduce"],
+        arg: Some("FILE"),
+        help: "shrink FILE to the smallest input with the same error",
+    },
+    Opt {
+        names: &["--difftest"],
+        arg: Some("COUNT"),
+        help: "check COUNT random expressions",
+    },
+    Opt {
+        names: &["--reference"],
+        arg: Some("CMD"),
+        help: "check difftest expressions against CMD",
+    },
+    Opt {
+        names: &["--capabilities"],
+        arg: None,
+        help: "list the features this calc has",
+    },
+    Opt {
+        names: &["-h", "--help"],
+        arg: None,
+        help: "show this help",
+    },
+    Opt {
+        names: &["-V", "--version"],
+        arg: None,
+        help: "show the version",
+    },
+];
+
+/// Everything the command line asked for
+#[derive(Default)]
+pub struct Options {
+    pub expressions: Vec<String>,
+    pub files: Vec<String>,
+    pub interactive: bool,
+    pub quiet: bool,
+    pub json: bool,
+    pub csv: bool,
+    pub speak_style: bool,
+    pub banner: bool,
+    pub precision: Option<usize>,
+    pub separator: Option<String>,
+    pub radix: Option<u32>,
+    pub timings: bool,
+    pub trace: bool,
+    pub symbolic: bool,
+    pub strict: bool,
+    pub no_warn: bool,
+    pub keypad: bool,
+    pub sticky: bool,
+    pub grammar: Option<String>,
+    pub no_rc: bool,
+    pub tutorial: bool,
+    pub drill: bool,
+    pub level: Option<u32>,
+    pub reduce: Option<String>,
+    pub difftest: Option<usize>,
+    pub reference: Option<String>,
+    pub capabilities: bool,
+    pub help: bool,
+    pub version: bool,
+}
+
+/// Sort out the arguments. Those given with -f or ending in '.calc'
+/// are files to evaluate, and other words left over make up one more
+/// expression, even those which happen to name files.
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut words = Vec::new();
+    while let Some(arg) = args.next() {
+        let Some(opt) = OPTIONS.iter().find(|o| o.names.contains(&arg.as_str())) else {
+            if arg.starts_with('-') {
+                return Err(format!("unknown option {}", arg));
+            }
+            if Path::new(&arg).extension().is_some_and(|e| e == "calc") {
+                options.files.push(arg);
+            } else {
+                words.push(arg);
+            }
+            continue;
+        };
+        let value = match opt.arg {
+            Some(name) => match args.next() {
+                Some(value) => value,
+                None => return Err(format!("{} needs {}", arg, name)),
+            },
+            None => String::new(),
+        };
+        let number = |min: usize, max: usize| match value.parse() {
+            Ok(n) if (min..=max).contains(&n) => Ok(n),
+            _ if max >= u32::MAX as usize => Err(format!("{} needs a number from {}", arg, min)),
+            _ => Err(format!("{} needs a number from {} to {}", arg, min, max)),
+        };
+        match opt.names[0] {
+            "-e" => options.expressions.push(value),
+            "-f" => options.files.push(value),
+            "-i" => options.interactive = true,
+            "-q" => options.quiet = true,
+            "--json" => options.json = true,
+            "--csv" => options.csv = true,
+            "--speak-style" => options.speak_style = true,
+            "--banner" => options.banner = true,
+            "--precision" => options.precision = Some(number(1, PRECISION)?),
+            "--separator" => options.separator = Some(value),
+            "--hex" => options.radix = Some(16),
+            "--oct" => options.radix = Some(8),
+            "--bin" => options.radix = Some(2),
+            "--timings" => options.timings = true,
+            "--trace" => options.trace = true,
+            "--symbolic" => options.symbolic = true,
+            "--strict" => options.strict = true,
+            "--no-warn" => options.no_warn = true,
+            "--keypad" => options.keypad = true,
+            "--sticky" => options.sticky = true,
+            "--grammar" => options.grammar = Some(value),
+            "--no-rc" => options.no_rc = true,
+            "tutorial" => options.tutorial = true,
+            "drill" => options.drill = true,
+            "--level" => options.level = Some(number(1, u32::MAX as usize)? as u32),
+            "--reduce" => options.reduce = Some(value),
+            "--difftest" => options.difftest = Some(number(0, usize::MAX)?),
+            "--reference" => options.reference = Some(value),
+            "--capabilities" => options.capabilities = true,
+            "-h" => options.help = true,
+            "-V" => options.version = true,
+            name => return Err(format!("option {} isn't handled", name)),
+        }
+    }
+    if !words.is_empty() {
+        options.expressions.push(words.join(" "));
+    }
+    Ok(options)
+}
+
+/// What --help prints
+pub fn usage() -> String {
+    let mut usage = String::from(
+        "usage: calc [options] [file.calc...] [expression]\n\n\
+         Evaluates the files and expressions given, or else stdin.\n\n",
+    );
+    let column = |o: &Opt| {
+        let names = o.names.join(", ");
+        match o.arg {
+            Some(arg) => format!("{} {}", names, arg),
+            None => names,
+        }
+    };
+    let width = OPTIONS.iter().map(|o| column(o).len()).max().unwrap_or(0);
+    for o in OPTIONS {
+        usage.push_str(&format!(
+            "  {:width$}  {}\n",
+            column(o),
+            o.help,
+            width = width
+        ));
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_option_is_handled() {
+        for opt in OPTIONS {
+            let mut args = vec![String::from(opt.names[0])];
+            if opt.arg.is_some() {
+                args.push(String::from("1"));
+            }
+            assert!(parse(args.into_iter()).is_ok(), "{}", opt.names[0]);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::interrupted;
+
+fn mulmod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn powmod(mut b: u64, mut e: u64, m: u64) -> u64 {
+    let mut r = 1;
+    b %= m;
+    while e > 0 {
+        if e & 1 == 1 {
+            r = mulmod(r, b, m);
+        }
+        b = mulmod(b, b, m);
+        e >>= 1;
+    }
+    r
+}
+
+/// Miller-Rabin with a set of bases which is exact for all u64
+pub fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for p in BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    'base: for a in BASES {
+        let mut x = powmod(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mulmod(x, x, n);
+            if x == n - 1 {
+                continue 'base;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Smallest prime greater than n
+pub fn next_prime(n: u64) -> Option<u64> {
+    let mut p = n.checked_add(1)?;
+    while !is_prime(p) {
+        p = p.checked_add(1)?;
+    }
+    Some(p)
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Find some non-trivial divisor of a composite number
+fn rho(n: u64) -> Result<u64, String> {
+    if n.is_multiple_of(2) {
+        return Ok(2);
+    }
+    let mut c = 1;
+    loop {
+        interrupted()?;
+        let f = |x| ((mulmod(x, x, n) as u128 + c) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = f(x);
+            y = f(f(y));
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return Ok(d);
+        }
+        c += 1;
+    }
+}
+
+/// Prime factors of n in increasing order, with repeats
+pub fn factor(n: u64) -> Result<Vec<u64>, String> {
+    let mut factors = Vec::new();
+    let mut pending = vec![n];
+    while let Some(m) = pending.pop() {
+        if m == 1 {
+            continue;
+        }
+        if is_prime(m) {
+            factors.push(m);
+        } else {
+            let d = rho(m)?;
+            pending.push(d);
+            pending.push(m / d);
+        }
+    }
+    factors.sort();
+    Ok(factors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_primes_from_composites() {
+        assert!(!is_prime(1));
+        assert!(is_prime(2));
+        assert!(is_prime(97));
+        assert!(!is_prime(561));
+        assert!(is_prime(18446744073709551557));
+        assert!(!is_prime(u64::MAX));
+    }
+
+    #[test]
+    fn finds_the_next_prime() {
+        assert_eq!(next_prime(100), Some(101));
+        assert_eq!(next_prime(2), Some(3));
+        assert_eq!(next_prime(u64::MAX), None);
+    }
+
+    #[test]
+    fn factors_in_order() {
+        assert_eq!(factor(360).unwrap(), [2, 2, 2, 3, 3, 5]);
+        assert_eq!(factor(1).unwrap(), []);
+        assert_eq!(factor(600851475143).unwrap(), [71, 839, 1471, 6857]);
+        assert_eq!(
+            factor(4294967291 * 4294967279).unwrap(),
+            [4294967279, 4294967291]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::units::{Quantity, NONE};
+
+/// Longest fraction written after the radix point
+const MAX_FRACTION: usize = 20;
+
+/// Literal prefix for each radix
+pub fn prefix(radix: u32) -> &'static str {
+    match radix {
+        2 => "0b",
+        8 => "0o",
+        16 => "0x",
+        _ => "",
+    }
+}
+
+/// Write a number in the given radix, using the same prefixes as the
+/// lexer accepts, e.g. "0xff" or "-0b10.1"
+pub fn format(q: &Quantity, radix: u32) -> Result<String, String> {
+    if q.dim != NONE {
+        return Err(String::from("cannot convert a value with units"));
+    }
+    if !q.val.is_finite() || q.val.abs() >= i128::MAX as f64 {
+        return Err(format!("cannot convert {}", q.val));
+    }
+    let sign = if q.val < 0.0 { "-" } else { "" };
+    let whole = match q.exact.and_then(|e| e.as_integer()) {
+        Some(n) => n.unsigned_abs(),
+        None => q.val.abs().trunc() as u128,
+    };
+    let mut digits = Vec::new();
+    let mut n = whole;
+    loop {
+        digits.push(std::char::from_digit((n % radix as u128) as u32, radix).unwrap_or('?'));
+        n /= radix as u128;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    let mut fraction = q.val.abs().fract();
+    if fraction != 0.0 {
+        digits.push('.');
+        for _ in 0..MAX_FRACTION {
+            fraction *= radix as f64;
+            let digit = fraction.trunc() as u32;
+            digits.push(std::char::from_digit(digit, radix).unwrap_or('?'));
+            fraction -= digit as f64;
+            if fraction == 0.0 {
+                break;
+            }
+        }
+    }
+    Ok(format!(
+        "{}{}{}",
+        sign,
+        prefix(radix),
+        digits.iter().collect::<String>()
+    ))
+}
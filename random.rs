@@ -0,0 +1,78 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A seed that differs from run to run
+pub fn clock() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// splitmix64 output function
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Pick from [lo, hi], drawing 64 random bits at a time from 'draw'
+/// and a second lot for spans too wide for the first
+fn pick(mut draw: impl FnMut() -> u64, lo: i128, hi: i128) -> i128 {
+    // hi - lo may not fit in an i128, but it always fits in a u128
+    let span = hi.wrapping_sub(lo) as u128;
+    let mut bits = draw() as u128;
+    if span > u64::MAX as u128 {
+        bits = bits << 64 | draw() as u128;
+    }
+    // Only the whole of i128 is too wide to count one more
+    let offset = match span.checked_add(1) {
+        Some(count) => bits % count,
+        None => bits,
+    };
+    lo.wrapping_add(offset as i128)
+}
+
+/// A splitmix64 sequence. Each calculator has its own, so seeding one
+/// leaves the others alone.
+#[derive(Clone, Copy)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// The sequence 'seed' starts, the same each time
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    /// Next 64 random bits
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        mix(self.0)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in [lo, hi]
+    pub fn range(&mut self, lo: i128, hi: i128) -> i128 {
+        pick(|| self.next(), lo, hi)
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use std::fmt;
+
+/// An exact fraction, always in lowest terms with a positive
+/// denominator. Operations return None when the result won't fit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(try_from = "(i128, i128)", into = "(i128, i128)")
+)]
+pub struct Rational {
+    num: i128,
+    den: i128,
+}
+
+/// Numerator and denominator, put in lowest terms, as read back when
+/// deserializing
+#[cfg(feature = "serde")]
+impl TryFrom<(i128, i128)> for Rational {
+    type Error = String;
+
+    fn try_from((num, den): (i128, i128)) -> Result<Rational, String> {
+        Rational::new(num, den).ok_or_else(|| format!("{}/{} is not a fraction", num, den))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Rational> for (i128, i128) {
+    fn from(r: Rational) -> (i128, i128) {
+        r.parts()
+    }
+}
+
+pub fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+// Arithmetic can overflow, so these return Options rather than being
+// the operator traits
+#[allow(clippy::should_implement_trait)]
+impl Rational {
+    fn new(num: i128, den: i128) -> Option<Rational> {
+        if den == 0 {
+            return None;
+        }
+        let g = gcd(num, den);
+        let (num, den) = (num / g, den / g);
+        if den < 0 {
+            Some(Rational {
+                num: num.checked_neg()?,
+                den: den.checked_neg()?,
+            })
+        } else {
+            Some(Rational { num, den })
+        }
+    }
+
+    pub fn integer(num: i128) -> Rational {
+        Rational { num, den: 1 }
+    }
+
+    /// The exact value of a binary floating point number
+    pub fn from_f64(val: f64) -> Option<Rational> {
+        if !val.is_finite() {
+            return None;
+        }
+        let mut num = val;
+        let mut den: i128 = 1;
+        while num.fract() != 0.0 {
+            num *= 2.0;
+            den = den.checked_mul(2)?;
+        }
+        if num.abs() >= i128::MAX as f64 {
+            return None;
+        }
+        Rational::new(num as i128, den)
+    }
+
+    /// Recover the short decimal a constant like 0.0254 was written as
+    pub fn from_decimal(val: f64) -> Option<Rational> {
+        let mut den: i128 = 1;
+        for _ in 0..=18 {
+            let num = val * den as f64;
+            if (num - num.round()).abs() <= 1e-9 * num.abs().max(1.0) {
+                return Rational::new(num.round() as i128, den);
+            }
+            den *= 10;
+        }
+        None
+    }
+
+    pub fn as_integer(self) -> Option<i128> {
+        if self.den == 1 {
+            Some(self.num)
+        } else {
+            None
+        }
+    }
+
+    /// Numerator and denominator
+    pub fn parts(self) -> (i128, i128) {
+        (self.num, self.den)
+    }
+
+    pub fn floor(self) -> Rational {
+        Rational::integer(self.num.div_euclid(self.den))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    pub fn add(self, other: Rational) -> Option<Rational> {
+        let g = gcd(self.den, other.den);
+        let den = (self.den / g).checked_mul(other.den)?;
+        let num = self
+            .num
+            .checked_mul(den / self.den)?
+            .checked_add(other.num.checked_mul(den / other.den)?)?;
+        Rational::new(num, den)
+    }
+
+    pub fn subtract(self, other: Rational) -> Option<Rational> {
+        self.add(other.negate()?)
+    }
+
+    pub fn times(self, other: Rational) -> Option<Rational> {
+        let a = gcd(self.num, other.den).max(1);
+        let b = gcd(other.num, self.den).max(1);
+        Rational::new(
+            (self.num / a).checked_mul(other.num / b)?,
+            (self.den / b).checked_mul(other.den / a)?,
+        )
+    }
+
+    pub fn divide(self, other: Rational) -> Option<Rational> {
+        self.times(Rational::new(other.den, other.num)?)
+    }
+
+    /// Raise to a whole power by repeated squaring
+    pub fn power(self, n: i128) -> Option<Rational> {
+        let mut base = if n < 0 {
+            Rational::new(self.den, self.num)?
+        } else {
+            self
+        };
+        let mut n = n.unsigned_abs();
+        let mut result = Rational::integer(1);
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.times(base)?;
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.times(base)?;
+            }
+        }
+        Some(result)
+    }
+
+    pub fn negate(self) -> Option<Rational> {
+        Some(Rational {
+            num: self.num.checked_neg()?,
+            den: self.den,
+        })
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
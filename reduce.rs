@@ -0,0 +1,77 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::evaluate;
+use std::panic;
+
+/// Delete runs of pieces, largest first, as long as 'keep' still
+/// holds for what is left
+fn shrink(mut pieces: Vec<String>, keep: &dyn Fn(&[String]) -> bool) -> Vec<String> {
+    let mut chunk = (pieces.len() / 2).max(1);
+    loop {
+        let mut removed = false;
+        let mut i = 0;
+        while i < pieces.len() {
+            let mut trial = pieces.clone();
+            trial.drain(i..(i + chunk).min(pieces.len()));
+            if keep(&trial) {
+                pieces = trial;
+                removed = true;
+            } else {
+                i += chunk;
+            }
+        }
+        if !removed {
+            if chunk == 1 {
+                return pieces;
+            }
+            chunk /= 2;
+        }
+    }
+}
+
+/// Shrink the input in 'path' to a small one which still fails the
+/// same way, first by lines and then by characters
+pub fn run(path: &str) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    // Panics are expected while reducing; don't report each one
+    panic::set_hook(Box::new(|_| {}));
+    let Err(target) = evaluate(&text) else {
+        let _ = panic::take_hook();
+        return Err(format!("{}: no error to reduce", path));
+    };
+    let same = |pieces: &[String]| evaluate(&pieces.concat()).as_ref().err() == Some(&target);
+    let lines = shrink(
+        text.split_inclusive('\n').map(String::from).collect(),
+        &same,
+    );
+    let chars = shrink(lines.concat().chars().map(String::from).collect(), &same);
+    let _ = panic::take_hook();
+    let reduced = chars.concat();
+    print!("{}", reduced);
+    if !reduced.ends_with('\n') {
+        println!();
+    }
+    eprintln!(
+        "reduced {} bytes to {}: {}",
+        text.len(),
+        reduced.len(),
+        target
+    );
+    Ok(())
+}
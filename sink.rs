@@ -0,0 +1,332 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::banner;
+use crate::format::Style;
+use crate::speak;
+use crate::units::NONE;
+use crate::value::Value;
+
+/// An input line and where it was found
+pub struct Input<'a> {
+    /// None when reading stdin or the command line
+    pub file: Option<&'a str>,
+    /// Counting from one
+    pub number: usize,
+    pub text: &'a str,
+    /// Where in the text an error was found, counting from one
+    pub column: Option<usize>,
+}
+
+/// Where an error is, for the start of its message: file, line and,
+/// when there is one, column
+fn place(input: &Input) -> Option<String> {
+    let file = input.file?;
+    Some(match input.column {
+        Some(column) => format!("{}:{}:{}", file, input.number, column),
+        None => format!("{}:{}", file, input.number),
+    })
+}
+
+/// The text with a caret under the column, to show where an error is
+fn caret(input: &Input) -> Option<String> {
+    let column = input.column?;
+    let mut caret: String = input
+        .text
+        .chars()
+        .take(column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    caret.push('^');
+    Some(format!("{}\n{}", input.text, caret))
+}
+
+/// Where results and errors go, so a front end can take values as
+/// they are instead of reading them back from text. Each comes with
+/// the input line it's from.
+pub trait ResultSink {
+    /// A line's result, to be written in 'style', with how long it
+    /// took in milliseconds when timings are on
+    fn result(&mut self, input: &Input, value: &Value, style: &Style, elapsed: Option<f64>);
+
+    /// A new value in the memory register
+    fn memory(&mut self, input: &Input, value: &Value, style: &Style);
+
+    fn error(&mut self, input: &Input, message: &str);
+
+    /// Something suspect about a result, reported after it
+    fn warning(&mut self, input: &Input, message: &str) {
+        self.error(input, &format!("warning: {}", message));
+    }
+
+    /// What a ':' command has to say
+    fn note(&mut self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+/// Print to stdout in one of the styles a person reads
+pub struct Stdout {
+    /// In words, for screen readers
+    pub speak_style: bool,
+
+    /// In large characters
+    pub banner: bool,
+}
+
+impl ResultSink for Stdout {
+    fn result(&mut self, _input: &Input, value: &Value, style: &Style, elapsed: Option<f64>) {
+        if self.speak_style {
+            print!("result is {}", speak::value(value));
+            if let Some(ms) = elapsed {
+                print!(
+                    ", in {} milliseconds",
+                    speak::value(&Value::number((ms * 1e3).round() / 1e3))
+                );
+            }
+            println!();
+        } else if self.banner {
+            print!("{}", banner::render(&value.styled(style).to_string()));
+            if let Some(ms) = elapsed {
+                println!("({:.3} ms)", ms);
+            }
+        } else {
+            print!("result = {}", value.styled(style));
+            if let Some(ms) = elapsed {
+                print!(" ({:.3} ms)", ms);
+            }
+            println!();
+        }
+    }
+
+    fn memory(&mut self, _input: &Input, value: &Value, style: &Style) {
+        if self.speak_style {
+            println!("memory is {}", speak::value(value));
+        } else {
+            println!("memory = {}", value.styled(style));
+        }
+    }
+
+    fn error(&mut self, input: &Input, message: &str) {
+        let message = match place(input) {
+            Some(place) => format!("{}: {}", place, message),
+            None => String::from(message),
+        };
+        if self.speak_style {
+            let message = match input.column {
+                Some(column) => format!("{} at column {}", message, column),
+                None => message,
+            };
+            println!("{}", speak::message(&message));
+        } else {
+            println!("{}", message);
+            if let Some(caret) = caret(input) {
+                println!("{}", caret);
+            }
+        }
+    }
+}
+
+/// Just the values, for scripts capturing the output; errors go to
+/// stderr so they don't end up mixed in
+pub struct Bare;
+
+impl ResultSink for Bare {
+    fn result(&mut self, _input: &Input, value: &Value, style: &Style, elapsed: Option<f64>) {
+        match elapsed {
+            Some(ms) => println!("{} ({:.3} ms)", value.styled(style), ms),
+            None => println!("{}", value.styled(style)),
+        }
+    }
+
+    fn memory(&mut self, _input: &Input, _value: &Value, _style: &Style) {}
+
+    fn error(&mut self, input: &Input, message: &str) {
+        match place(input) {
+            Some(place) => eprintln!("{}: {}", place, message),
+            None => eprintln!("{}", message),
+        }
+        if let Some(caret) = caret(input) {
+            eprintln!("{}", caret);
+        }
+    }
+}
+
+/// Text as a JSON string
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A number as JSON, which has no way to write infinities or NaN
+fn number(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        quote(&x.to_string())
+    }
+}
+
+/// Plain numbers and arrays of them as JSON numbers, at full precision,
+/// and anything else as the text it would print as
+fn json(value: &Value, style: &Style) -> String {
+    match value {
+        Value::Scalar(q) if q.dim == NONE => number(q.val),
+        Value::Vector(v) => format!(
+            "[{}]",
+            v.iter().map(|x| number(*x)).collect::<Vec<_>>().join(", ")
+        ),
+        Value::Matrix(m) => format!(
+            "[{}]",
+            m.iter()
+                .map(|row| json(&Value::Vector(row.clone()), style))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => quote(&value.styled(style).to_string()),
+    }
+}
+
+/// One JSON object per line, for other programs to read
+pub struct Json;
+
+impl ResultSink for Json {
+    fn result(&mut self, input: &Input, value: &Value, style: &Style, elapsed: Option<f64>) {
+        print!(
+            "{{\"input\": {}, \"value\": {}",
+            quote(input.text),
+            json(value, style)
+        );
+        if let Some(ms) = elapsed {
+            print!(", \"ms\": {}", ms);
+        }
+        println!("}}");
+    }
+
+    fn memory(&mut self, input: &Input, value: &Value, style: &Style) {
+        println!(
+            "{{\"input\": {}, \"memory\": {}}}",
+            quote(input.text),
+            json(value, style)
+        );
+    }
+
+    fn error(&mut self, input: &Input, message: &str) {
+        print!(
+            "{{\"input\": {}, \"error\": {}",
+            quote(input.text),
+            quote(message)
+        );
+        if let Some(file) = input.file {
+            print!(", \"file\": {}, \"line\": {}", quote(file), input.number);
+        }
+        if let Some(column) = input.column {
+            print!(", \"column\": {}", column);
+        }
+        println!("}}");
+    }
+
+    fn warning(&mut self, input: &Input, message: &str) {
+        println!(
+            "{{\"input\": {}, \"warning\": {}}}",
+            quote(input.text),
+            quote(message)
+        );
+    }
+    fn note(&mut self, text: &str) {
+        println!("{{\"note\": {}}}", quote(text));
+    }
+}
+
+/// A CSV field, quoted when it needs to be
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        String::from(text)
+    }
+}
+
+/// Rows of line number, expression, result and error, for data
+/// pipelines, under a header row
+#[derive(Default)]
+pub struct Csv {
+    header: bool,
+}
+
+impl Csv {
+    pub fn new() -> Csv {
+        Csv::default()
+    }
+
+    fn row(&mut self, input: &Input, result: &str, error: &str) {
+        if !self.header {
+            println!("line_number,expression,result,error");
+            self.header = true;
+        }
+        println!(
+            "{},{},{},{}",
+            input.number,
+            field(input.text),
+            field(result),
+            field(error)
+        );
+    }
+}
+
+impl ResultSink for Csv {
+    fn result(&mut self, input: &Input, value: &Value, style: &Style, _elapsed: Option<f64>) {
+        self.row(input, &value.styled(style).to_string(), "");
+    }
+
+    fn memory(&mut self, input: &Input, value: &Value, style: &Style) {
+        self.row(input, &value.styled(style).to_string(), "");
+    }
+
+    fn error(&mut self, input: &Input, message: &str) {
+        self.row(input, "", message);
+    }
+    /// Notes don't fit the columns, so they go to stderr
+    fn note(&mut self, text: &str) {
+        eprintln!("{}", text);
+    }
+}
+
+/// Only errors, on stderr, for the startup file
+pub struct Silent;
+
+impl ResultSink for Silent {
+    fn result(&mut self, _input: &Input, _value: &Value, _style: &Style, _elapsed: Option<f64>) {}
+
+    fn memory(&mut self, _input: &Input, _value: &Value, _style: &Style) {}
+
+    fn error(&mut self, input: &Input, message: &str) {
+        Bare.error(input, message);
+    }
+
+    fn note(&mut self, _text: &str) {}
+}
@@ -0,0 +1,252 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::units::{Dim, Quantity, NONE};
+use crate::value::Value;
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+const SCALES: [(u64, &str); 4] = [
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+/// SI base units, in dimension vector order
+const UNITS: [&str; 7] = [
+    "meter", "kilogram", "second", "ampere", "kelvin", "mole", "candela",
+];
+
+/// Whole numbers, e.g. "one thousand two hundred thirty four"
+fn integer(n: u64) -> String {
+    for (scale, name) in SCALES {
+        if n >= scale {
+            let rest = n % scale;
+            let head = format!("{} {}", integer(n / scale), name);
+            return if rest == 0 {
+                head
+            } else {
+                format!("{} {}", head, integer(rest))
+            };
+        }
+    }
+    if n >= 100 {
+        let rest = n % 100;
+        let head = format!("{} hundred", ONES[(n / 100) as usize]);
+        return if rest == 0 {
+            head
+        } else {
+            format!("{} {}", head, integer(rest))
+        };
+    }
+    if n >= 20 {
+        let rest = n % 10;
+        return if rest == 0 {
+            TENS[(n / 10) as usize].to_string()
+        } else {
+            format!("{} {}", TENS[(n / 10) as usize], ONES[rest as usize])
+        };
+    }
+    ONES[n as usize].to_string()
+}
+
+/// A decimal like "3.14", reading the fraction digit by digit
+fn decimal(s: &str) -> String {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    let mut words = integer(whole.parse().unwrap_or(0));
+    if !fraction.is_empty() {
+        words.push_str(" point");
+        for d in fraction.chars().filter_map(|d| d.to_digit(10)) {
+            words.push(' ');
+            words.push_str(ONES[d as usize]);
+        }
+    }
+    words
+}
+
+/// Any number; very large and small ones are read in scientific
+/// notation, "three point one four times ten to the minus two"
+pub fn number(x: f64) -> String {
+    if x.is_nan() {
+        return String::from("not a number");
+    }
+    let sign = if x < 0.0 { "minus " } else { "" };
+    let x = x.abs();
+    if x.is_infinite() {
+        return format!("{}infinity", sign);
+    }
+    if x == 0.0 || (1e-3..1e6).contains(&x) {
+        return format!("{}{}", sign, decimal(&format!("{}", x)));
+    }
+    let sci = format!("{:e}", x);
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let exp: i64 = exp.parse().unwrap_or(0);
+    format!(
+        "{}{} times ten to the {}{}",
+        sign,
+        decimal(mantissa),
+        if exp < 0 { "minus " } else { "" },
+        integer(exp.unsigned_abs())
+    )
+}
+
+/// One unit with its power, "seconds squared"
+fn unit(name: &str, exp: i8, plural: bool) -> String {
+    let s = if plural { "s" } else { "" };
+    match exp {
+        1 => format!("{}{}", name, s),
+        2 => format!("{}{} squared", name, s),
+        3 => format!("{}{} cubed", name, s),
+        _ => format!("{}{} to the {}", name, s, integer(exp as u64)),
+    }
+}
+
+/// Units, "meters per second squared"
+fn units(dim: Dim, plural: bool) -> String {
+    let num: Vec<(usize, i8)> = dim
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| **e > 0)
+        .map(|(i, e)| (i, *e))
+        .collect();
+    let mut words: Vec<String> = num
+        .iter()
+        .enumerate()
+        .map(|(n, (i, e))| unit(UNITS[*i], *e, plural && n == num.len() - 1))
+        .collect();
+    for (i, e) in dim.iter().enumerate() {
+        if *e < 0 {
+            words.push(format!("per {}", unit(UNITS[i], -e, false)));
+        }
+    }
+    words.join(" ")
+}
+
+fn quantity(q: &Quantity) -> String {
+    if q.dim == NONE {
+        number(q.val)
+    } else {
+        format!("{} {}", number(q.val), units(q.dim, q.val != 1.0))
+    }
+}
+
+fn list(v: &[f64]) -> String {
+    v.iter().map(|x| number(*x)).collect::<Vec<_>>().join(", ")
+}
+
+pub fn value(v: &Value) -> String {
+    match v {
+        Value::Scalar(q) => quantity(q),
+        Value::Vector(v) => format!("vector of {}: {}", integer(v.len() as u64), list(v)),
+        Value::Matrix(m) => {
+            let rows: Vec<String> = m
+                .iter()
+                .enumerate()
+                .map(|(r, row)| format!("row {}: {}", integer(r as u64 + 1), list(row)))
+                .collect();
+            format!(
+                "matrix of {} rows; {}",
+                integer(m.len() as u64),
+                rows.join("; ")
+            )
+        }
+        Value::Text(t) => t.clone(),
+        Value::Date(t) => format!("date {}", crate::date::Date(*t)),
+        Value::Symbolic(p) => p.to_string(),
+    }
+}
+
+/// Replace the symbols in an error message with words
+pub fn message(m: &str) -> String {
+    let mut words = String::new();
+    for c in m.chars() {
+        match c {
+            '+' => words.push_str(" plus "),
+            '-' => words.push_str(" minus "),
+            '*' => words.push_str(" times "),
+            '/' => words.push_str(" per "),
+            '^' => words.push_str(" to the "),
+            ':' => words.push(','),
+            c => words.push(c),
+        }
+    }
+    words.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_numbers_in_words() {
+        assert_eq!(integer(1234), "one thousand two hundred thirty four");
+        assert_eq!(number(-3.25), "minus three point two five");
+        assert_eq!(number(0.0), "zero");
+        assert_eq!(
+            number(3.14e-7),
+            "three point one four times ten to the minus seven"
+        );
+    }
+
+    #[test]
+    fn reads_units_in_words() {
+        let q = Quantity::new(9.8)
+            .times(Quantity::unit("m").unwrap())
+            .and_then(|q| q.divide(Quantity::unit("s").unwrap()))
+            .and_then(|q| q.divide(Quantity::unit("s").unwrap()))
+            .unwrap();
+        assert_eq!(
+            value(&Value::Scalar(q)),
+            "nine point eight meters per second squared"
+        );
+    }
+
+    #[test]
+    fn reads_symbols_in_messages() {
+        assert_eq!(
+            message("incompatible units: m/s + s"),
+            "incompatible units, m per s plus s"
+        );
+    }
+}
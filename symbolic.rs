@@ -0,0 +1,335 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::format::Style;
+use crate::interrupted;
+use crate::rational::Rational;
+use crate::units::{Quantity, NONE};
+use std::cmp::Reverse;
+use std::fmt;
+
+/// Variables multiplied together, each with its power, sorted by name
+type Monomial = Vec<(String, u32)>;
+
+/// Highest degree a term may have, so that 'x^4000000000' fails
+/// rather than working for ever
+const MAX_DEGREE: u64 = 10_000;
+
+/// Sum of the powers in a term
+fn degree(m: &Monomial) -> u64 {
+    m.iter().map(|(_, p)| *p as u64).sum()
+}
+
+fn too_high() -> String {
+    format!("degree is more than {}", MAX_DEGREE)
+}
+
+/// Higher degrees first, then higher powers of earlier names, so
+/// x^2 comes before x*y
+fn order(m: &Monomial) -> (Reverse<u32>, Vec<&str>) {
+    let names = m
+        .iter()
+        .flat_map(|(n, p)| (0..*p).map(move |_| n.as_str()))
+        .collect::<Vec<_>>();
+    (Reverse(names.len() as u32), names)
+}
+
+/// A polynomial in named variables, with like terms combined and zero
+/// terms dropped. Terms are kept highest degree first, the order they
+/// are written in.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Poly {
+    terms: Vec<(Monomial, Quantity)>,
+}
+
+impl Poly {
+    pub fn var(name: &str) -> Poly {
+        Poly {
+            terms: vec![(
+                vec![(String::from(name), 1)],
+                Quantity::rational(Rational::integer(1)),
+            )],
+        }
+    }
+
+    pub fn constant(q: Quantity) -> Poly {
+        let mut terms = vec![(Vec::new(), q)];
+        terms.retain(|(_, q)| q.val != 0.0);
+        Poly { terms }
+    }
+
+    /// The value when no variables are left
+    pub fn as_constant(&self) -> Option<Quantity> {
+        match self.terms.as_slice() {
+            [] => Some(Quantity::rational(Rational::integer(0))),
+            [(m, q)] if m.is_empty() => Some(*q),
+            _ => None,
+        }
+    }
+
+    /// Sort the terms and combine those with the same variables
+    fn normalize(mut self) -> Result<Poly, String> {
+        self.terms.sort_by(|(a, _), (b, _)| order(a).cmp(&order(b)));
+        let mut terms: Vec<(Monomial, Quantity)> = Vec::new();
+        for (m, q) in self.terms {
+            match terms.last_mut() {
+                Some((last, sum)) if *last == m => *sum = sum.add(q)?,
+                _ => terms.push((m, q)),
+            }
+        }
+        terms.retain(|(_, q)| q.val != 0.0);
+        Ok(Poly { terms })
+    }
+
+    pub fn add(self, other: Poly) -> Result<Poly, String> {
+        let mut terms = self.terms;
+        terms.extend(other.terms);
+        Poly { terms }.normalize()
+    }
+
+    pub fn negate(self) -> Poly {
+        Poly {
+            terms: self
+                .terms
+                .into_iter()
+                .map(|(m, q)| (m, q.negate()))
+                .collect(),
+        }
+    }
+
+    pub fn times(&self, other: &Poly) -> Result<Poly, String> {
+        let mut terms = Vec::new();
+        for (a, p) in &self.terms {
+            for (b, q) in &other.terms {
+                let mut m = a.clone();
+                for (name, power) in b {
+                    match m.iter_mut().find(|(n, _)| n == name) {
+                        Some((_, p)) => *p = p.checked_add(*power).ok_or_else(too_high)?,
+                        None => m.push((name.clone(), *power)),
+                    }
+                }
+                if degree(&m) > MAX_DEGREE {
+                    return Err(too_high());
+                }
+                m.sort();
+                terms.push((m, p.times(*q)?));
+            }
+        }
+        Poly { terms }.normalize()
+    }
+
+    pub fn divide(self, q: Quantity) -> Result<Poly, String> {
+        Ok(Poly {
+            terms: self
+                .terms
+                .into_iter()
+                .map(|(m, p)| Ok((m, p.divide(q)?)))
+                .collect::<Result<_, String>>()?,
+        })
+    }
+
+    /// Build c[0]*name^n + ... + c[n], highest power first
+    pub fn from_coefficients(name: &str, c: &[f64]) -> Result<Poly, String> {
+        let terms = c
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(power, c)| {
+                let m = if power == 0 {
+                    Vec::new()
+                } else {
+                    vec![(String::from(name), power as u32)]
+                };
+                (m, Quantity::new(*c))
+            })
+            .collect();
+        Poly { terms }.normalize()
+    }
+
+    /// Coefficients of a polynomial in at most one name, highest
+    /// power first
+    pub fn coefficients(&self) -> Result<Vec<Quantity>, String> {
+        let mut names = self.terms.iter().flat_map(|(m, _)| m).map(|(n, _)| n);
+        if let Some(first) = names.next() {
+            if names.any(|n| n != first) {
+                return Err(format!("{} has more than one name in it", self));
+            }
+        }
+        let power = |m: &Monomial| m.first().map_or(0, |(_, p)| *p) as usize;
+        let degree = self.terms.first().map_or(0, |(m, _)| power(m));
+        let mut c = vec![Quantity::rational(Rational::integer(0)); degree + 1];
+        for (m, q) in &self.terms {
+            c[degree - power(m)] = *q;
+        }
+        Ok(c)
+    }
+
+    /// Value at 'x' by Horner's rule
+    pub fn at(&self, x: Quantity) -> Result<Quantity, String> {
+        let c = self.coefficients()?;
+        let mut v = c[0];
+        for c in &c[1..] {
+            v = v.times(x)?.add(*c)?;
+        }
+        Ok(v)
+    }
+
+    /// Real roots for degrees one through three, in increasing order
+    pub fn roots(&self) -> Result<Vec<f64>, String> {
+        let c = self.coefficients()?;
+        if c.iter().any(|q| q.dim != c[0].dim) {
+            return Err(String::from("coefficients have different units"));
+        }
+        let c: Vec<f64> = c.iter().map(|q| q.val / c[0].val).collect();
+        let mut roots = match c.as_slice() {
+            [_, b] => vec![-b],
+            [_, b, c] => {
+                let d = b * b - 4.0 * c;
+                if d < 0.0 {
+                    Vec::new()
+                } else {
+                    // Avoid cancellation by taking the larger root first
+                    let q = -0.5 * (b + b.signum() * d.sqrt());
+                    if q == 0.0 {
+                        vec![0.0, 0.0]
+                    } else {
+                        vec![q, c / q]
+                    }
+                }
+            }
+            [_, a, b, c] => {
+                // Depressed cubic t^3 + pt + q, with x = t - a/3
+                let p = b - a * a / 3.0;
+                let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+                let shift = a / 3.0;
+                let d = q * q / 4.0 + p * p * p / 27.0;
+                if d > 0.0 {
+                    let u = (-q / 2.0 + d.sqrt()).cbrt();
+                    let v = (-q / 2.0 - d.sqrt()).cbrt();
+                    vec![u + v - shift]
+                } else if p == 0.0 {
+                    vec![-shift; 3]
+                } else {
+                    let r = 2.0 * (-p / 3.0).sqrt();
+                    let phi = (3.0 * q / (p * r)).clamp(-1.0, 1.0).acos() / 3.0;
+                    (0..3)
+                        .map(|k| {
+                            r * (phi - 2.0 * std::f64::consts::PI * k as f64 / 3.0).cos() - shift
+                        })
+                        .collect()
+                }
+            }
+            [_] => return Err(format!("{} has no name to solve for", self)),
+            _ => return Err(String::from("roots only handles degrees up to three")),
+        };
+        roots.sort_by(f64::total_cmp);
+        // Adding zero turns -0 into 0
+        Ok(roots.into_iter().map(|r| r + 0.0).collect())
+    }
+
+    /// Only whole, non-negative powers leave a polynomial
+    pub fn power(&self, n: Quantity) -> Result<Poly, String> {
+        let whole = n
+            .exact
+            .filter(|_| n.dim == NONE)
+            .and_then(|e| e.as_integer());
+        let n = match whole.and_then(|n| u32::try_from(n).ok()) {
+            Some(n) => n,
+            None => return Err(format!("cannot raise {} to {}", self, n)),
+        };
+        let most = self.terms.iter().map(|(m, _)| degree(m)).max().unwrap_or(0);
+        if most * n as u64 > MAX_DEGREE {
+            return Err(too_high());
+        }
+        let mut result = Poly::constant(Quantity::rational(Rational::integer(1)));
+        for _ in 0..n {
+            interrupted()?;
+            result = result.times(self)?;
+        }
+        Ok(result)
+    }
+
+    /// Write the terms with their coefficients in 'style'
+    pub fn write(&self, f: &mut fmt::Formatter, style: &Style) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (i, (m, q)) in self.terms.iter().enumerate() {
+            let mut q = *q;
+            if q.val < 0.0 {
+                q = q.negate();
+                write!(f, "{}", if i == 0 { "-" } else { " - " })?;
+            } else if i > 0 {
+                write!(f, " + ")?;
+            }
+            // Leave out a coefficient of one, unless it's all there is
+            let one = q.val == 1.0 && q.dim == NONE;
+            if !one || m.is_empty() {
+                q.write(f, style)?;
+            }
+            for (j, (name, power)) in m.iter().enumerate() {
+                if j > 0 || !one {
+                    write!(f, "*")?;
+                }
+                write!(f, "{}", name)?;
+                if *power != 1 {
+                    write!(f, "^{}", power)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &Style::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(n: i128) -> Quantity {
+        Quantity::rational(Rational::integer(n))
+    }
+
+    #[test]
+    fn expands_and_combines_terms() {
+        let x = Poly::var("x");
+        let square = x.clone().add(Poly::constant(number(1))).unwrap();
+        assert_eq!(
+            square.power(number(2)).unwrap().to_string(),
+            "x^2 + 2*x + 1"
+        );
+        let y = Poly::var("y");
+        let sum = x.clone().add(y.clone()).unwrap();
+        let difference = x.clone().add(y.negate()).unwrap();
+        assert_eq!(sum.times(&difference).unwrap().to_string(), "x^2 - y^2");
+        let zero = x.clone().add(x.negate()).unwrap();
+        assert_eq!(zero.as_constant(), Some(number(0)));
+    }
+
+    #[test]
+    fn limits_the_degree() {
+        let error = Poly::var("x").power(number(20000)).unwrap_err();
+        assert_eq!(error, "degree is more than 10000");
+    }
+}
@@ -0,0 +1,92 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use calc::sink::{Bare, ResultSink, Silent};
+use calc::Calc;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::Cursor;
+
+thread_local! {
+    // Per thread, so the test harness's own allocations don't count
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count() {
+    let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+}
+
+/// Count heap allocations, so the test can see what a line costs
+struct Counter;
+
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counter = Counter;
+
+/// Allocations made evaluating 'line' 'count' times, once the stacks
+/// have grown on a first pass
+fn allocations(line: &str, count: usize, sink: fn() -> Box<dyn ResultSink>) -> usize {
+    let run = |count| {
+        let text = format!("{}\n", line).repeat(count);
+        let mut calc = Calc::new(Box::new(Cursor::new(text.into_bytes())));
+        calc.sink = sink();
+        let before = ALLOCATIONS.with(Cell::get);
+        calc.lines();
+        ALLOCATIONS.with(Cell::get) - before
+    };
+    run(count) - run(1)
+}
+
+/// Lines of numbers and units shouldn't touch the heap
+#[test]
+fn per_line() {
+    for line in ["1+2", "2 m * 3 s", "(1+2)*3^2", "-4 / 2"] {
+        assert_eq!(
+            allocations(line, 1000, || Box::new(Silent)),
+            0,
+            "allocations for {}",
+            line
+        );
+    }
+}
+
+/// Nor should printing them. The test harness's capture of stdout
+/// grows now and then, but anything made per line would show up as a
+/// thousand.
+#[test]
+fn printed() {
+    for line in ["1+2", "2 m * 3 s", "1234567 / 8"] {
+        let count = allocations(line, 1000, || Box::new(Bare));
+        assert!(count < 100, "{} allocations for {}", count, line);
+    }
+}
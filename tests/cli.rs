@@ -0,0 +1,82 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run the binary on 'input' and return what it printed to stdout
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_calc"))
+        .args(["--no-rc"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn json_has_an_object_per_line() {
+    let output = run(&["--json"], "1+2\n2 m\n1/0\n[1,2]\n:precision 3\n");
+    assert_eq!(
+        output,
+        concat!(
+            "{\"input\": \"1+2\", \"value\": 3}\n",
+            "{\"input\": \"2 m\", \"value\": \"2 m\"}\n",
+            "{\"input\": \"1/0\", \"error\": \"division by zero\", \"column\": 2}\n",
+            "{\"input\": \"[1,2]\", \"value\": [1, 2]}\n",
+            "{\"note\": \"precision = 3\"}\n",
+        )
+    );
+}
+
+#[test]
+fn csv_has_a_row_per_line() {
+    let output = run(&["--csv"], "1+2\n2 m\n\"a,b\" + 1\n");
+    assert_eq!(
+        output,
+        concat!(
+            "line_number,expression,result,error\n",
+            "1,1+2,3,\n",
+            "2,2 m,2 m,\n",
+            "3,\"\"\"a,b\"\" + 1\",,cannot compute with text\n",
+        )
+    );
+}
+
+#[test]
+fn continues_inside_brackets_and_after_operators() {
+    assert_eq!(
+        run(&["-q"], "(1 +\n2)\n3 *\n4\n[1,\n2]\n"),
+        "3\n12\n[1, 2]\n"
+    );
+}
+
+#[test]
+fn expressions_given_apart_stay_apart() {
+    assert_eq!(run(&["-q", "-e", "1 +", "-e", "2"], ""), "2\n");
+}
@@ -0,0 +1,99 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::builtins::BUILTINS;
+use crate::units::{Dim, NONE};
+use crate::value::Value;
+use crate::Calc;
+use crate::ENonTerminal::Line;
+use std::io::Write;
+
+struct Step {
+    text: &'static str,
+    hint: &'static str,
+    check: fn(&Value) -> bool,
+}
+
+/// Does 'v' have the given value and units?
+fn is(v: &Value, val: f64, dim: Dim) -> bool {
+    match v {
+        Value::Scalar(q) => q.dim == dim && (q.val - val).abs() <= 1e-9 * val.abs(),
+        _ => false,
+    }
+}
+
+const STEPS: &[Step] = &[
+    Step {
+        text: "Type an expression and press enter to evaluate it. Multiplication\n\
+               happens before addition, as usual. Compute 2 plus 3 times 4.",
+        hint: "2 + 3 * 4",
+        check: |v| is(v, 14.0, NONE),
+    },
+    Step {
+        text: "Parentheses group things. Add 2 and 3 first, then multiply by 4.",
+        hint: "(2 + 3) * 4",
+        check: |v| is(v, 20.0, NONE),
+    },
+    Step {
+        text: "Numbers can have units, which combine as you compute and are\n\
+               checked when added. How fast is 100 km covered in 2 h?",
+        hint: "100 km / 2 h",
+        check: |v| is(v, 100e3 / 7200.0, [1, 0, -1, 0, 0, 0, 0]),
+    },
+    Step {
+        text: "Functions are called with their arguments in parentheses. Find\n\
+               the largest of 3, 9 and 4 with max.",
+        hint: "max(3, 9, 4)",
+        check: |v| is(v, 9.0, NONE),
+    },
+    Step {
+        text: "Square brackets make vectors, and many functions work on them.\n\
+               Add up the vector of 1, 2, 3 and 4 with sum.",
+        hint: "sum([1, 2, 3, 4])",
+        check: |v| is(v, 10.0, NONE),
+    },
+];
+
+/// Walk through the basics, checking each answer with the evaluator
+pub fn run(calc: &mut Calc) {
+    println!("Welcome to calc. Press ctrl-D at any time to leave.");
+    for (i, step) in STEPS.iter().enumerate() {
+        println!();
+        println!("Step {} of {}: {}", i + 1, STEPS.len(), step.text);
+        loop {
+            print!("> ");
+            let _ = std::io::stdout().flush();
+            let results = calc.results;
+            if !calc.parse(Line) {
+                println!();
+                return;
+            }
+            if calc.results > results && (step.check)(&calc.ans) {
+                println!("Correct!");
+                break;
+            }
+            println!("Not quite; try typing: {}", step.hint);
+        }
+    }
+    let names: Vec<&str> = BUILTINS.iter().map(|b| b.name).collect();
+    println!();
+    println!(
+        "That's the tour. Other functions to try: {}",
+        names.join(", ")
+    );
+}
@@ -0,0 +1,329 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::format::{self, Style};
+use crate::rational::Rational;
+use std::fmt;
+
+/// Names of the SI base units, in dimension vector order
+const BASE: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+/// Exponent of each SI base unit
+pub type Dim = [i8; 7];
+
+/// A dimensionless quantity
+pub const NONE: Dim = [0; 7];
+
+const LENGTH: Dim = [1, 0, 0, 0, 0, 0, 0];
+const AREA: Dim = [2, 0, 0, 0, 0, 0, 0];
+const VOLUME: Dim = [3, 0, 0, 0, 0, 0, 0];
+const MASS: Dim = [0, 1, 0, 0, 0, 0, 0];
+pub const TIME: Dim = [0, 0, 1, 0, 0, 0, 0];
+const CURRENT: Dim = [0, 0, 0, 1, 0, 0, 0];
+const TEMPERATURE: Dim = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: Dim = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: Dim = [0, 0, 0, 0, 0, 0, 1];
+const FREQUENCY: Dim = [0, 0, -1, 0, 0, 0, 0];
+const FORCE: Dim = [1, 1, -2, 0, 0, 0, 0];
+const PRESSURE: Dim = [-1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dim = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dim = [2, 1, -3, 0, 0, 0, 0];
+const CHARGE: Dim = [0, 0, 1, 1, 0, 0, 0];
+const VOLTAGE: Dim = [2, 1, -3, -1, 0, 0, 0];
+const RESISTANCE: Dim = [2, 1, -3, -2, 0, 0, 0];
+
+/// Every unit the lexer knows, with its size in base units
+const UNITS: &[(&str, f64, Dim)] = &[
+    ("m", 1.0, LENGTH),
+    ("km", 1e3, LENGTH),
+    ("cm", 1e-2, LENGTH),
+    ("mm", 1e-3, LENGTH),
+    ("um", 1e-6, LENGTH),
+    ("nm", 1e-9, LENGTH),
+    ("in", 0.0254, LENGTH),
+    ("ft", 0.3048, LENGTH),
+    ("yd", 0.9144, LENGTH),
+    ("mi", 1609.344, LENGTH),
+    ("ha", 1e4, AREA),
+    ("L", 1e-3, VOLUME),
+    ("mL", 1e-6, VOLUME),
+    ("kg", 1.0, MASS),
+    ("g", 1e-3, MASS),
+    ("mg", 1e-6, MASS),
+    ("t", 1e3, MASS),
+    ("lb", 0.45359237, MASS),
+    ("oz", 0.028349523125, MASS),
+    ("s", 1.0, TIME),
+    ("ms", 1e-3, TIME),
+    ("us", 1e-6, TIME),
+    ("ns", 1e-9, TIME),
+    ("min", 60.0, TIME),
+    ("h", 3600.0, TIME),
+    ("d", 86400.0, TIME),
+    ("A", 1.0, CURRENT),
+    ("mA", 1e-3, CURRENT),
+    ("K", 1.0, TEMPERATURE),
+    ("mol", 1.0, AMOUNT),
+    ("cd", 1.0, LUMINOSITY),
+    ("Hz", 1.0, FREQUENCY),
+    ("kHz", 1e3, FREQUENCY),
+    ("MHz", 1e6, FREQUENCY),
+    ("N", 1.0, FORCE),
+    ("Pa", 1.0, PRESSURE),
+    ("kPa", 1e3, PRESSURE),
+    ("J", 1.0, ENERGY),
+    ("kJ", 1e3, ENERGY),
+    ("kWh", 3.6e6, ENERGY),
+    ("W", 1.0, POWER),
+    ("kW", 1e3, POWER),
+    ("C", 1.0, CHARGE),
+    ("V", 1.0, VOLTAGE),
+    ("mV", 1e-3, VOLTAGE),
+    ("ohm", 1.0, RESISTANCE),
+];
+
+/// A number along with the dimensions of its units. 'exact' shadows
+/// 'val' with rational arithmetic for as long as that's possible.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quantity {
+    pub val: f64,
+    pub dim: Dim,
+    pub exact: Option<Rational>,
+}
+
+impl Quantity {
+    /// A dimensionless number with no exact value
+    pub fn new(val: f64) -> Quantity {
+        Quantity {
+            val,
+            dim: NONE,
+            exact: None,
+        }
+    }
+
+    /// A dimensionless number known exactly
+    pub fn rational(exact: Rational) -> Quantity {
+        Quantity {
+            val: exact.to_f64(),
+            dim: NONE,
+            exact: Some(exact),
+        }
+    }
+
+    /// Find a unit by name, returning one of it
+    pub fn unit(name: &str) -> Option<Quantity> {
+        UNITS
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|&(_, val, dim)| Quantity {
+                val,
+                dim,
+                exact: Rational::from_decimal(val),
+            })
+    }
+
+    fn check(self, other: Quantity, op: &str) -> Result<(), String> {
+        if self.dim == other.dim {
+            Ok(())
+        } else {
+            Err(format!(
+                "incompatible units: {} {} {}",
+                Units(self.dim),
+                op,
+                Units(other.dim)
+            ))
+        }
+    }
+
+    pub fn add(self, other: Quantity) -> Result<Quantity, String> {
+        self.check(other, "+")?;
+        Ok(Quantity {
+            val: self.val + other.val,
+            dim: self.dim,
+            exact: self.exact.zip(other.exact).and_then(|(a, b)| a.add(b)),
+        })
+    }
+
+    pub fn subtract(self, other: Quantity) -> Result<Quantity, String> {
+        self.check(other, "-")?;
+        Ok(Quantity {
+            val: self.val - other.val,
+            dim: self.dim,
+            exact: self.exact.zip(other.exact).and_then(|(a, b)| a.subtract(b)),
+        })
+    }
+
+    pub fn times(self, other: Quantity) -> Result<Quantity, String> {
+        let mut dim = self.dim;
+        for (d, o) in dim.iter_mut().zip(other.dim) {
+            *d = d.checked_add(o).ok_or_else(range)?;
+        }
+        Ok(Quantity {
+            val: self.val * other.val,
+            dim,
+            exact: self.exact.zip(other.exact).and_then(|(a, b)| a.times(b)),
+        })
+    }
+
+    pub fn divide(self, other: Quantity) -> Result<Quantity, String> {
+        let mut dim = self.dim;
+        for (d, o) in dim.iter_mut().zip(other.dim) {
+            *d = d.checked_sub(o).ok_or_else(range)?;
+        }
+        Ok(Quantity {
+            val: self.val / other.val,
+            dim,
+            exact: self.exact.zip(other.exact).and_then(|(a, b)| a.divide(b)),
+        })
+    }
+
+    /// Units can only be raised to whole powers
+    pub fn power(self, other: Quantity) -> Result<Quantity, String> {
+        if other.dim != NONE {
+            return Err(format!("exponent has units: {}", Units(other.dim)));
+        }
+        let whole = other.exact.and_then(|e| e.as_integer());
+        let mut dim = self.dim;
+        if self.dim != NONE {
+            let n = whole
+                .and_then(|n| i8::try_from(n).ok())
+                .ok_or_else(|| String::from("units need a whole exponent"))?;
+            for d in dim.iter_mut() {
+                *d = d
+                    .checked_mul(n)
+                    .ok_or_else(|| String::from("exponent too large for units"))?;
+            }
+        }
+        Ok(Quantity {
+            val: self.val.powf(other.val),
+            dim,
+            exact: self.exact.zip(whole).and_then(|(a, n)| a.power(n)),
+        })
+    }
+
+    pub fn negate(self) -> Quantity {
+        Quantity {
+            val: -self.val,
+            dim: self.dim,
+            exact: self.exact.and_then(|a| a.negate()),
+        }
+    }
+
+    /// Write the number in 'style', followed by its units
+    pub fn write(&self, f: &mut fmt::Formatter, style: &Style) -> fmt::Result {
+        format::number(f, self.val, style)?;
+        if self.dim != NONE {
+            write!(f, " {}", Units(self.dim))?;
+        }
+        Ok(())
+    }
+}
+
+/// The error for units whose exponents don't fit
+pub(crate) fn range() -> String {
+    String::from("unit exponent out of range")
+}
+
+/// Display adaptor for a dimension vector, e.g. "kg m/s^2"
+struct Units(Dim);
+
+impl Units {
+    /// Write the units whose exponents have the given sign, negated
+    /// when they're being written below a '/'
+    fn write(&self, f: &mut fmt::Formatter, sign: i8, flip: bool) -> fmt::Result {
+        let mut sep = "";
+        for (name, exp) in BASE.iter().zip(self.0) {
+            if exp.signum() == sign {
+                let exp = if flip { -exp } else { exp };
+                write!(f, "{}{}", sep, name)?;
+                if exp != 1 {
+                    write!(f, "^{}", exp)?;
+                }
+                sep = " ";
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let num = self.0.iter().any(|e| *e > 0);
+        let den = self.0.iter().any(|e| *e < 0);
+        match (num, den) {
+            (false, false) => write!(f, "1"),
+            (false, true) => self.write(f, -1, false),
+            (true, false) => self.write(f, 1, false),
+            (true, true) => {
+                self.write(f, 1, false)?;
+                write!(f, "/")?;
+                self.write(f, -1, true)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &Style::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    fn unit(name: &str) -> Quantity {
+        Quantity::unit(name).unwrap()
+    }
+
+    fn show(q: Quantity) -> String {
+        Value::Scalar(q).to_string()
+    }
+
+    #[test]
+    fn converts_to_base_units() {
+        assert_eq!(show(unit("km").add(unit("m")).unwrap()), "1001 m");
+        assert_eq!(
+            unit("km").add(unit("m")).unwrap().exact.unwrap().to_f64(),
+            1001.0
+        );
+        assert!(Quantity::unit("furlong").is_none());
+    }
+
+    #[test]
+    fn multiplies_dimensions() {
+        let speed = unit("m").divide(unit("s")).unwrap();
+        let force = unit("kg").times(speed).unwrap().divide(unit("s")).unwrap();
+        assert_eq!(show(force), "1 m kg/s^2");
+        let area = unit("m")
+            .power(Quantity::rational(Rational::integer(2)))
+            .unwrap();
+        assert_eq!(area.dim, unit("ha").dim);
+    }
+
+    #[test]
+    fn rejects_mismatched_units() {
+        let error = unit("m").add(unit("s")).unwrap_err();
+        assert_eq!(error, "incompatible units: m + s");
+        assert!(unit("m").power(Quantity::new(0.5)).is_err());
+        assert!(Quantity::new(2.0).power(unit("s")).is_err());
+    }
+}
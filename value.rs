@@ -0,0 +1,487 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::date::{self, DAY};
+use crate::format::{self, Style};
+use crate::symbolic::Poly;
+use crate::units::{Quantity, NONE, TIME};
+use std::fmt;
+
+/// Everything which can live on the value stack
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Scalar(Quantity),
+    Vector(Vec<f64>),
+    Matrix(Vec<Vec<f64>>),
+    Text(String),
+    // Seconds since 1970-01-01 UTC
+    Date(f64),
+    // Still has unbound names in it
+    Symbolic(Poly),
+}
+use Value::*;
+
+/// Array elements are plain numbers
+fn plain(q: Quantity) -> Result<f64, String> {
+    if q.dim == NONE {
+        Ok(q.val)
+    } else {
+        Err(String::from("arrays cannot hold units"))
+    }
+}
+
+/// Text can be printed but not computed with, and dates only
+/// move by durations
+fn numbers(a: &Value, b: &Value) -> Result<(), String> {
+    match (a, b) {
+        (Text(_), _) | (_, Text(_)) => Err(String::from("cannot compute with text")),
+        (Date(_), _) | (_, Date(_)) => Err(String::from("dates can only be added and subtracted")),
+        _ => Ok(()),
+    }
+}
+
+/// Both sides as polynomials, when at least one of them has names in it
+fn symbols(a: &Value, b: &Value) -> Option<(Poly, Poly)> {
+    match (a, b) {
+        (Symbolic(a), Symbolic(b)) => Some((a.clone(), b.clone())),
+        (Symbolic(a), Scalar(b)) => Some((a.clone(), Poly::constant(*b))),
+        (Scalar(a), Symbolic(b)) => Some((Poly::constant(*a), b.clone())),
+        _ => None,
+    }
+}
+
+/// Back to a number once every name has cancelled out
+fn symbolic(p: Poly) -> Value {
+    match p.as_constant() {
+        Some(q) => Scalar(q),
+        None => Symbolic(p),
+    }
+}
+
+/// A time to move a date by, where plain numbers count days
+fn duration(q: Quantity) -> Result<f64, String> {
+    match q.dim {
+        NONE => Ok(q.val * DAY),
+        TIME => Ok(q.val),
+        _ => Err(String::from("dates move by days or times")),
+    }
+}
+
+/// Apply 'f' to matching elements of two vectors
+fn zip(a: &[f64], b: &[f64], f: fn(f64, f64) -> f64) -> Result<Vec<f64>, String> {
+    if a.len() != b.len() {
+        return Err(format!("length mismatch: {} and {}", a.len(), b.len()));
+    }
+    Ok(a.iter().zip(b).map(|(x, y)| f(*x, *y)).collect())
+}
+
+/// Apply 'f' to matching elements of two matrices
+fn zip2(a: &[Vec<f64>], b: &[Vec<f64>], f: fn(f64, f64) -> f64) -> Result<Vec<Vec<f64>>, String> {
+    if a.len() != b.len() {
+        return Err(format!("row mismatch: {} and {}", a.len(), b.len()));
+    }
+    a.iter().zip(b).map(|(x, y)| zip(x, y, f)).collect()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> Result<f64, String> {
+    Ok(zip(a, b, |x, y| x * y)?.iter().sum())
+}
+
+fn column(m: &[Vec<f64>], c: usize) -> Vec<f64> {
+    m.iter().map(|row| row[c]).collect()
+}
+
+fn columns(m: &[Vec<f64>]) -> usize {
+    m.first().map_or(0, |row| row.len())
+}
+
+fn matmul(a: &[Vec<f64>], b: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, String> {
+    let cols: Vec<Vec<f64>> = (0..columns(b)).map(|c| column(b, c)).collect();
+    a.iter()
+        .map(|row| cols.iter().map(|col| dot(row, col)).collect())
+        .collect()
+}
+
+fn square(m: &[Vec<f64>]) -> Result<usize, String> {
+    if m.iter().all(|row| row.len() == m.len()) {
+        Ok(m.len())
+    } else {
+        Err(String::from("matrix is not square"))
+    }
+}
+
+/// Swap the largest remaining entry in column 'c' into row 'c'
+fn pivot(m: &mut [Vec<f64>], c: usize) -> bool {
+    let mut p = c;
+    for r in c + 1..m.len() {
+        if m[r][c].abs() > m[p][c].abs() {
+            p = r;
+        }
+    }
+    m.swap(c, p);
+    p != c
+}
+
+// Arithmetic can fail, so these return Results rather than being the
+// operator traits
+#[allow(clippy::should_implement_trait)]
+impl Value {
+    pub fn number(val: f64) -> Value {
+        Scalar(Quantity::new(val))
+    }
+
+    /// The first number in this which is infinite or NaN
+    pub fn non_finite(&self) -> Option<f64> {
+        match self {
+            Scalar(q) => Some(q.val),
+            Vector(v) => v.iter().copied().find(|x| !x.is_finite()),
+            Matrix(m) => m.iter().flatten().copied().find(|x| !x.is_finite()),
+            Date(t) => Some(*t),
+            Text(_) | Symbolic(_) => None,
+        }
+        .filter(|x| !x.is_finite())
+    }
+
+    /// Build a vector or matrix from the elements of a '[...]' literal
+    pub fn array(elements: &[Value]) -> Result<Value, String> {
+        if let Some(Vector(_)) = elements.first() {
+            let mut rows = Vec::new();
+            for element in elements {
+                match element {
+                    Vector(row) => rows.push(row.clone()),
+                    _ => return Err(String::from("matrix rows must be vectors")),
+                }
+            }
+            if rows.iter().any(|row| row.len() != rows[0].len()) {
+                return Err(String::from("matrix rows differ in length"));
+            }
+            return Ok(Matrix(rows));
+        }
+        let mut vector = Vec::new();
+        for element in elements {
+            match element {
+                Scalar(q) => vector.push(plain(*q)?),
+                _ => return Err(String::from("vector elements must be numbers")),
+            }
+        }
+        Ok(Vector(vector))
+    }
+
+    pub fn add(self, other: Value) -> Result<Value, String> {
+        match (&self, &other) {
+            (Date(t), Scalar(q)) | (Scalar(q), Date(t)) => {
+                return Ok(Date(date::check(t + duration(*q)?)?))
+            }
+            _ => numbers(&self, &other)?,
+        }
+        if let Some((a, b)) = symbols(&self, &other) {
+            return Ok(symbolic(a.add(b)?));
+        }
+        match (self, other) {
+            (Scalar(a), Scalar(b)) => Ok(Scalar(a.add(b)?)),
+            (Vector(a), Vector(b)) => Ok(Vector(zip(&a, &b, |x, y| x + y)?)),
+            (Matrix(a), Matrix(b)) => Ok(Matrix(zip2(&a, &b, |x, y| x + y)?)),
+            _ => Err(String::from("cannot add values of different shapes")),
+        }
+    }
+
+    /// The difference of two dates is in days
+    pub fn subtract(self, other: Value) -> Result<Value, String> {
+        match (&self, &other) {
+            (Date(a), Date(b)) => return Ok(Value::number((a - b) / DAY)),
+            (Date(t), Scalar(q)) => return Ok(Date(date::check(t - duration(*q)?)?)),
+            _ => numbers(&self, &other)?,
+        }
+        if let Some((a, b)) = symbols(&self, &other) {
+            return Ok(symbolic(a.add(b.negate())?));
+        }
+        match (self, other) {
+            (Scalar(a), Scalar(b)) => Ok(Scalar(a.subtract(b)?)),
+            (Vector(a), Vector(b)) => Ok(Vector(zip(&a, &b, |x, y| x - y)?)),
+            (Matrix(a), Matrix(b)) => Ok(Matrix(zip2(&a, &b, |x, y| x - y)?)),
+            _ => Err(String::from("cannot subtract values of different shapes")),
+        }
+    }
+
+    /// Multiply every element by 's'
+    fn scale(self, s: f64) -> Result<Value, String> {
+        Ok(match self {
+            Scalar(q) => Scalar(q.times(Quantity::new(s))?),
+            Vector(v) => Vector(v.iter().map(|x| x * s).collect()),
+            Matrix(m) => Matrix(
+                m.iter()
+                    .map(|row| row.iter().map(|x| x * s).collect())
+                    .collect(),
+            ),
+            a => a,
+        })
+    }
+
+    /// Scalars scale arrays, vectors dot, matrices multiply
+    pub fn times(self, other: Value) -> Result<Value, String> {
+        numbers(&self, &other)?;
+        if let Some((a, b)) = symbols(&self, &other) {
+            return Ok(symbolic(a.times(&b)?));
+        }
+        match (self, other) {
+            (Scalar(a), Scalar(b)) => Ok(Scalar(a.times(b)?)),
+            (Scalar(a), b) => b.scale(plain(a)?),
+            (a, Scalar(b)) => a.scale(plain(b)?),
+            (Vector(a), Vector(b)) => Ok(Value::number(dot(&a, &b)?)),
+            (Matrix(a), Matrix(b)) => Ok(Matrix(matmul(&a, &b)?)),
+            (Matrix(a), Vector(b)) => Ok(Vector(
+                a.iter().map(|row| dot(row, &b)).collect::<Result<_, _>>()?,
+            )),
+            (Vector(a), Matrix(b)) => Ok(Vector(
+                (0..columns(&b))
+                    .map(|c| dot(&a, &column(&b, c)))
+                    .collect::<Result<_, _>>()?,
+            )),
+            _ => Err(String::from("cannot multiply these values")),
+        }
+    }
+
+    pub fn divide(self, other: Value) -> Result<Value, String> {
+        numbers(&self, &other)?;
+        if matches!(&other, Scalar(b) if b.val == 0.0) {
+            return Err(String::from("division by zero"));
+        }
+        match (self, other) {
+            (Scalar(a), Scalar(b)) => Ok(Scalar(a.divide(b)?)),
+            (Symbolic(a), Scalar(b)) => Ok(symbolic(a.divide(b)?)),
+            (_, Symbolic(b)) => Err(format!("cannot divide by {}", b)),
+            (a, Scalar(b)) => a.scale(1.0 / plain(b)?),
+            _ => Err(String::from("can only divide by a number")),
+        }
+    }
+
+    pub fn power(self, other: Value) -> Result<Value, String> {
+        numbers(&self, &other)?;
+        match (self, other) {
+            (Scalar(a), Scalar(b)) => Ok(Scalar(a.power(b)?)),
+            (Symbolic(a), Scalar(b)) => Ok(symbolic(a.power(b)?)),
+            _ => Err(String::from("can only raise numbers to powers")),
+        }
+    }
+
+    pub fn negate(self) -> Result<Value, String> {
+        match self {
+            Scalar(q) => Ok(Scalar(q.negate())),
+            Symbolic(p) => Ok(Symbolic(p.negate())),
+            Text(_) => Err(String::from("cannot negate text")),
+            Date(_) => Err(String::from("cannot negate a date")),
+            a => a.scale(-1.0),
+        }
+    }
+
+    /// Whether two numbers or dates are within 'relative' of the
+    /// larger or 'absolute', whichever is looser
+    pub fn approx(&self, other: &Value, relative: f64, absolute: f64) -> Result<bool, String> {
+        let (a, b) = match (self, other) {
+            (Scalar(a), Scalar(b)) => {
+                a.subtract(*b)?;
+                (a.val, b.val)
+            }
+            (Date(a), Date(b)) => (*a, *b),
+            _ => return Err(String::from("can only compare numbers or dates")),
+        };
+        Ok(a == b || (a - b).abs() <= (relative * a.abs().max(b.abs())).max(absolute))
+    }
+
+    pub fn transpose(&self) -> Result<Value, String> {
+        match self {
+            Matrix(m) => Ok(Matrix((0..columns(m)).map(|c| column(m, c)).collect())),
+            Vector(v) => Ok(Vector(v.clone())),
+            _ => Err(String::from("transpose needs a matrix")),
+        }
+    }
+
+    /// Determinant by Gaussian elimination
+    pub fn det(&self) -> Result<Value, String> {
+        let Matrix(m) = self else {
+            return Err(String::from("det needs a matrix"));
+        };
+        let mut m = m.clone();
+        let n = square(&m)?;
+        let mut det = 1.0;
+        for c in 0..n {
+            if pivot(&mut m, c) {
+                det = -det;
+            }
+            if m[c][c] == 0.0 {
+                return Ok(Value::number(0.0));
+            }
+            det *= m[c][c];
+            let (top, rest) = m.split_at_mut(c + 1);
+            let p = &top[c];
+            for row in rest {
+                let f = row[c] / p[c];
+                for (x, y) in row[c..].iter_mut().zip(&p[c..]) {
+                    *x -= f * y;
+                }
+            }
+        }
+        Ok(Value::number(det))
+    }
+
+    /// Inverse by Gauss-Jordan elimination
+    pub fn inv(&self) -> Result<Value, String> {
+        let Matrix(m) = self else {
+            return Err(String::from("inv needs a matrix"));
+        };
+        let mut m = m.clone();
+        let n = square(&m)?;
+        for (r, row) in m.iter_mut().enumerate() {
+            row.extend((0..n).map(|c| if c == r { 1.0 } else { 0.0 }));
+        }
+        for c in 0..n {
+            pivot(&mut m, c);
+            let p = m[c][c];
+            if p == 0.0 {
+                return Err(String::from("matrix is singular"));
+            }
+            for x in m[c].iter_mut() {
+                *x /= p;
+            }
+            let p = m[c].clone();
+            for (r, row) in m.iter_mut().enumerate() {
+                if r != c {
+                    let f = row[c];
+                    for (x, y) in row.iter_mut().zip(&p) {
+                        *x -= f * y;
+                    }
+                }
+            }
+        }
+        Ok(Matrix(m.into_iter().map(|row| row[n..].to_vec()).collect()))
+    }
+}
+
+fn write_vector(f: &mut fmt::Formatter, v: &[f64], style: &Style) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, x) in v.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        format::number(f, *x, style)?;
+    }
+    write!(f, "]")
+}
+
+impl Value {
+    /// Display the value with numbers written in 'style'
+    pub fn styled<'a>(&'a self, style: &'a Style) -> Styled<'a> {
+        Styled(self, style)
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, style: &Style) -> fmt::Result {
+        match self {
+            Scalar(q) => q.write(f, style),
+            Vector(v) => write_vector(f, v, style),
+            Matrix(m) => {
+                write!(f, "[")?;
+                for (i, row) in m.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_vector(f, row, style)?;
+                }
+                write!(f, "]")
+            }
+            Text(t) => write!(f, "{}", t),
+            Date(t) => write!(f, "{}", date::Date(*t)),
+            Symbolic(p) => p.write(f, style),
+        }
+    }
+}
+
+/// Display adaptor for a value in a session's style
+pub struct Styled<'a>(&'a Value, &'a Style);
+
+impl fmt::Display for Styled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.write(f, self.1)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &Style::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array(rows: &[&[f64]]) -> Value {
+        let rows: Vec<Value> = rows
+            .iter()
+            .map(|row| Value::array(&row.iter().map(|x| Value::number(*x)).collect::<Vec<_>>()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        Value::array(&rows).unwrap()
+    }
+
+    #[test]
+    fn builds_vectors_and_matrices() {
+        let v = Value::array(&[Value::number(1.0), Value::number(2.0)]).unwrap();
+        assert_eq!(v, Vector(vec![1.0, 2.0]));
+        assert_eq!(
+            array(&[&[1.0, 2.0], &[3.0, 4.0]]).to_string(),
+            "[[1, 2], [3, 4]]"
+        );
+        let ragged = [Vector(vec![1.0]), Vector(vec![1.0, 2.0])];
+        assert!(Value::array(&ragged).is_err());
+        let metres = Scalar(Quantity::unit("m").unwrap());
+        assert!(Value::array(&[metres]).is_err());
+    }
+
+    #[test]
+    fn multiplies_arrays() {
+        let m = array(&[&[1.0, 2.0], &[3.0, 4.0]]);
+        let v = Vector(vec![1.0, 1.0]);
+        assert_eq!(m.clone().times(v.clone()).unwrap(), Vector(vec![3.0, 7.0]));
+        assert_eq!(v.clone().times(v.clone()).unwrap(), Value::number(2.0));
+        assert_eq!(
+            m.clone().times(Value::number(2.0)).unwrap(),
+            array(&[&[2.0, 4.0], &[6.0, 8.0]])
+        );
+        assert!(v.add(Vector(vec![1.0])).is_err());
+        assert_eq!(m.det().unwrap(), Value::number(-2.0));
+        let d = array(&[&[2.0, 0.0], &[0.0, 4.0]]);
+        assert_eq!(d.inv().unwrap(), array(&[&[0.5, 0.0], &[0.0, 0.25]]));
+        assert!(array(&[&[1.0, 2.0], &[2.0, 4.0]]).inv().is_err());
+    }
+
+    #[test]
+    fn moves_dates_by_days_or_times() {
+        let date = |text| Date(date::parse(text).unwrap());
+        let leap = date("2024-02-28").add(Value::number(1.0)).unwrap();
+        assert_eq!(leap.to_string(), "2024-02-29");
+        let hours = Scalar(Quantity::unit("h").unwrap());
+        let later = date("2024-02-28").add(hours).unwrap();
+        assert_eq!(later.to_string(), "2024-02-28 01:00");
+        let days = date("2024-03-01").subtract(date("2023-03-01")).unwrap();
+        assert_eq!(days, Value::number(366.0));
+        let metres = Scalar(Quantity::unit("m").unwrap());
+        assert!(date("2024-02-28").add(metres).is_err());
+        assert!(date("2024-02-28").negate().is_err());
+    }
+}
@@ -0,0 +1,35 @@
+/*
+ * Copyright © 2024 Keith Packard <keithp@keithp.com>
+ *
+ * This program is free software; you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful, but
+ * WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ * General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License along
+ * with this program; if not, write to the Free Software Foundation, Inc.,
+ * 51 Franklin St, Fifth Floor, Boston, MA 02110-1301, USA.
+ */
+
+use crate::EvalContext;
+use wasm_bindgen::prelude::*;
+
+/// Evaluate each line of 'input' for a web page, returning the last
+/// result as it would print, or empty if no line had a value. Errors
+/// are thrown as JavaScript exceptions.
+#[wasm_bindgen]
+pub fn eval(input: &str) -> Result<String, JsError> {
+    let mut context = EvalContext::new();
+    let values = context
+        .eval_str(input)
+        .map_err(|error| JsError::new(&error.to_string()))?;
+    Ok(values
+        .last()
+        .map(|value| value.styled(context.style()).to_string())
+        .unwrap_or_default())
+}